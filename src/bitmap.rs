@@ -0,0 +1,101 @@
+//! Helpers for turning RGBA pixel buffers into GDI bitmaps
+
+use std::{ffi::c_void, mem, ptr};
+use windows::Win32::{
+    Foundation::*,
+    Graphics::Gdi::*,
+    UI::WindowsAndMessaging::{CreateIconIndirect, HICON, ICONINFO},
+};
+
+use super::error::{Error, Result};
+
+/// Checks `rgba` holds exactly `width` × `height` pixels
+pub(crate) fn check_rgba(rgba: &[u8], width: u32, height: u32) -> Result<()> {
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidImage("the image is empty"));
+    }
+    if rgba.len() as u64 != width as u64 * height as u64 * 4 {
+        return Err(Error::InvalidImage(
+            "the buffer doesn't hold width × height RGBA pixels",
+        ));
+    }
+    Ok(())
+}
+
+/// Reorders RGBA pixels into the BGRA GDI expects,
+/// premultiplying by alpha if asked
+pub(crate) fn rgba_to_bgra(rgba: &[u8], premultiply: bool) -> Vec<u8> {
+    let mut bgra = Vec::with_capacity(rgba.len());
+    for pixel in rgba.chunks_exact(4) {
+        let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+        let scale = |c: u8| match premultiply {
+            true => ((c as u16 * a as u16 + 127) / 255) as u8,
+            false => c,
+        };
+        bgra.extend_from_slice(&[scale(b), scale(g), scale(r), a]);
+    }
+    bgra
+}
+
+/// A top-down 32bpp DIB section holding `bgra`
+pub(crate) fn create_dib(width: u32, height: u32, bgra: &[u8]) -> Result<HBITMAP> {
+    let info = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width as i32,
+            // negative for top-down rows
+            biHeight: -(height as i32),
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut bits: *mut c_void = ptr::null_mut();
+    unsafe {
+        let bitmap = CreateDIBSection(None, &info, DIB_RGB_COLORS, &mut bits, None, 0)?;
+        if bits.is_null() {
+            let _ = DeleteObject(bitmap);
+            return Err(Error::Drawing("CreateDIBSection"));
+        }
+        ptr::copy_nonoverlapping(bgra.as_ptr(), bits as *mut u8, bgra.len());
+        Ok(bitmap)
+    }
+}
+
+/// An icon, or a cursor with its hotspot at `hotspot`, from RGBA
+/// pixels. The caller owns it and destroys it when done with it.
+pub(crate) fn create_icon(
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    hotspot: Option<(u32, u32)>,
+) -> Result<HICON> {
+    check_rgba(rgba, width, height)?;
+    let color = create_dib(width, height, &rgba_to_bgra(rgba, false))?;
+    // the alpha channel is used instead, but a mask is still needed;
+    // monochrome rows are padded to a multiple of 16 bits
+    let mask_bits = vec![0u8; width.div_ceil(16) as usize * 2 * height as usize];
+    unsafe {
+        let mask = CreateBitmap(
+            width as i32,
+            height as i32,
+            1,
+            1,
+            Some(mask_bits.as_ptr() as *const c_void),
+        );
+        let (x, y) = hotspot.unwrap_or_default();
+        let info = ICONINFO {
+            fIcon: BOOL::from(hotspot.is_none()),
+            xHotspot: x,
+            yHotspot: y,
+            hbmMask: mask,
+            hbmColor: color,
+        };
+        let icon = CreateIconIndirect(&info);
+        let _ = DeleteObject(mask);
+        let _ = DeleteObject(color);
+        Ok(icon?)
+    }
+}
@@ -0,0 +1,66 @@
+//! Child windows of any [`Win`] type inside another window.
+//!
+//! A child window is clipped to its parent's client area, and its
+//! parent doesn't draw over it. It's destroyed along with its
+//! parent, its state being dropped once any handles to it have
+//! been dropped too, so a handle to a child can be kept or
+//! dropped as the parent sees fit.
+//!
+//! For windows of documents that can be moved, tiled and cascaded
+//! inside a frame window, see [`mdi`](super::mdi).
+
+use windows::Win32::{Foundation::*, UI::WindowsAndMessaging::*};
+
+use super::{error::Result, handle::WinHandle, win::Win, win_create_args::WinCreateArgs, WPModule};
+
+/// Creates and shows a child window of type `C` inside `parent`.
+/// `create_args` should give it a position and size, as with
+/// [`WinCreateArgs::default_child`].
+pub fn create_child<C: Win>(
+    parent: HWND,
+    title: &str,
+    create_args: &WinCreateArgs,
+) -> Result<WinHandle<C>> {
+    let mut create_args = create_args.clone().with_parent(parent);
+    create_args.style |= WS_CHILD;
+    create_args.style &= !WS_POPUP;
+
+    // so the parent doesn't paint over its children
+    unsafe {
+        let style = GetWindowLongPtrW(parent, GWL_STYLE);
+        SetWindowLongPtrW(parent, GWL_STYLE, style | WS_CLIPCHILDREN.0 as isize);
+    }
+
+    let child = WinHandle::new(C::new(WPModule::new().get_hinstance()));
+    child.create_window_with_args(title, &create_args)?;
+    child.show();
+    child.update();
+    Ok(child)
+}
+
+/// The direct children of `hwnd`, from the top of the z-order down
+pub(crate) fn children(hwnd: HWND) -> Vec<HWND> {
+    let mut children = Vec::new();
+    let mut child = unsafe { GetWindow(hwnd, GW_CHILD) };
+    while let Ok(hwnd) = child {
+        children.push(hwnd);
+        child = unsafe { GetWindow(hwnd, GW_HWNDNEXT) };
+    }
+    children
+}
+
+/// Moves `hwnd` to the top or bottom of its siblings
+pub(crate) fn set_z_order(hwnd: HWND, insert_after: HWND) -> Result<()> {
+    unsafe {
+        SetWindowPos(
+            hwnd,
+            insert_after,
+            0,
+            0,
+            0,
+            0,
+            SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,264 @@
+//! Custom window chrome.
+//!
+//! A window with a custom frame (see
+//! [`WinCreateArgs::with_custom_frame`](super::win_create_args::WinCreateArgs::with_custom_frame))
+//! has no standard title bar or border, so it can draw its own in
+//! `on_paint`. [`Win::on_hit_test`](super::Win::on_hit_test) tells
+//! Windows which parts of it act as the caption, resize edges and
+//! caption buttons, so dragging, resizing, snapping and the buttons
+//! still work natively.
+
+use std::cell::{Cell, RefCell};
+use windows::Win32::{
+    Foundation::*,
+    Graphics::{Dwm::DwmExtendFrameIntoClientArea, Gdi::*},
+    UI::{Controls::MARGINS, WindowsAndMessaging::*},
+};
+
+use super::{dpi, error::Result, hword, lword, win::Win, Event, EventHandled};
+
+/// The frame, size limits, click-through regions and units of a window.
+/// They're shared with the window's cell, so the queries Windows
+/// sends while the window is borrowed, such as the `WM_NCCALCSIZE`
+/// from a handler's `SetWindowPos`, still get the window's answers.
+#[derive(Debug, Default)]
+pub(crate) struct Chrome {
+    pub(crate) custom_frame: Cell<bool>,
+    pub(crate) min_size: Cell<Option<(i32, i32)>>,
+    pub(crate) max_size: Cell<Option<(i32, i32)>>,
+    pub(crate) click_through_regions: RefCell<Vec<RECT>>,
+    pub(crate) logical_units: Cell<bool>,
+}
+
+impl Chrome {
+    /// `point`, in device pixels, in the units the window works in
+    pub(crate) fn to_window_units(&self, hwnd: HWND, point: POINT) -> POINT {
+        match self.logical_units.get() {
+            true => {
+                let dpi = dpi::window_dpi(hwnd);
+                POINT {
+                    x: dpi::to_logical(point.x, dpi),
+                    y: dpi::to_logical(point.y, dpi),
+                }
+            }
+            false => point,
+        }
+    }
+}
+
+/// What part of a window a point is over
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HitTest {
+    Client,
+    /// Drags the window, and maximizes it on a double click
+    Caption,
+    Left,
+    Right,
+    Top,
+    Bottom,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    MinButton,
+    MaxButton,
+    CloseButton,
+    SystemMenu,
+    /// Passed on to the window underneath
+    Transparent,
+    Nowhere,
+}
+
+impl HitTest {
+    fn code(self) -> isize {
+        (match self {
+            HitTest::Client => HTCLIENT,
+            HitTest::Caption => HTCAPTION,
+            HitTest::Left => HTLEFT,
+            HitTest::Right => HTRIGHT,
+            HitTest::Top => HTTOP,
+            HitTest::Bottom => HTBOTTOM,
+            HitTest::TopLeft => HTTOPLEFT,
+            HitTest::TopRight => HTTOPRIGHT,
+            HitTest::BottomLeft => HTBOTTOMLEFT,
+            HitTest::BottomRight => HTBOTTOMRIGHT,
+            HitTest::MinButton => HTMINBUTTON,
+            HitTest::MaxButton => HTMAXBUTTON,
+            HitTest::CloseButton => HTCLOSE,
+            HitTest::SystemMenu => HTSYSMENU,
+            HitTest::Transparent => return HTTRANSPARENT as isize,
+            HitTest::Nowhere => HTNOWHERE,
+        }) as isize
+    }
+}
+
+/// The resize edge `point` is on, within `border` of the edges
+/// of `rect`, or `Client` if it isn't near any of them
+pub fn edge_hit_test(point: POINT, rect: &RECT, border: i32) -> HitTest {
+    let left = point.x < rect.left + border;
+    let right = point.x >= rect.right - border;
+    let top = point.y < rect.top + border;
+    let bottom = point.y >= rect.bottom - border;
+    match (left, right, top, bottom) {
+        (true, _, true, _) => HitTest::TopLeft,
+        (_, true, true, _) => HitTest::TopRight,
+        (true, _, _, true) => HitTest::BottomLeft,
+        (_, true, _, true) => HitTest::BottomRight,
+        (true, ..) => HitTest::Left,
+        (_, true, ..) => HitTest::Right,
+        (_, _, true, _) => HitTest::Top,
+        (.., true) => HitTest::Bottom,
+        _ => HitTest::Client,
+    }
+}
+
+/// The width of the sizing border the standard frame would have
+fn frame_thickness() -> (i32, i32) {
+    unsafe {
+        let padding = GetSystemMetrics(SM_CXPADDEDBORDER);
+        (
+            GetSystemMetrics(SM_CXSIZEFRAME) + padding,
+            GetSystemMetrics(SM_CYSIZEFRAME) + padding,
+        )
+    }
+}
+
+/// Extends the DWM frame `left`, `right`, `top` and `bottom` pixels
+/// into the client area, or over all of it with `-1`s
+pub(crate) fn extend_frame(hwnd: HWND, left: i32, right: i32, top: i32, bottom: i32) -> Result<()> {
+    let margins = MARGINS {
+        cxLeftWidth: left,
+        cxRightWidth: right,
+        cyTopHeight: top,
+        cyBottomHeight: bottom,
+    };
+    unsafe {
+        DwmExtendFrameIntoClientArea(hwnd, &margins)?;
+    }
+    Ok(())
+}
+
+/// Answers `WM_NCCALCSIZE`, `WM_GETMINMAXINFO` and `WM_NCHITTEST`
+/// from the shared state alone, for when the window is borrowed
+pub(crate) fn answer_borrowed(chrome: &Chrome, event: &Event) -> EventHandled {
+    match event.message {
+        WM_NCCALCSIZE => calc_size(chrome, event),
+        WM_GETMINMAXINFO => min_max_info(chrome, event),
+        // on_hit_test can't be asked, but the frame still works
+        WM_NCHITTEST => hit_test_with(chrome, event, |_| None),
+        _ => EventHandled::NotHandled,
+    }
+}
+
+/// Handles `WM_NCHITTEST`, checking the click-through regions,
+/// then `on_hit_test`, then the resize edges of a custom frame
+pub(crate) fn hit_test<W: Win>(win: &mut W, event: &Event) -> EventHandled {
+    let chrome = win.get_base().chrome.clone();
+    hit_test_with(&chrome, event, |point| win.on_hit_test(point))
+}
+
+fn hit_test_with(
+    chrome: &Chrome,
+    event: &Event,
+    on_hit_test: impl FnOnce(POINT) -> Option<HitTest>,
+) -> EventHandled {
+    let mut point = POINT {
+        x: lword(event.lparam.0) as i16 as i32,
+        y: hword(event.lparam.0) as i16 as i32,
+    };
+    let _ = unsafe { ScreenToClient(event.hwnd, &mut point) };
+    let window_point = chrome.to_window_units(event.hwnd, point);
+
+    let click_through = chrome
+        .click_through_regions
+        .borrow()
+        .iter()
+        .any(|rect| unsafe { PtInRect(rect, window_point) }.as_bool());
+
+    let hit = match click_through {
+        true => Some(HitTest::Transparent),
+        false => on_hit_test(window_point),
+    };
+    let mut rect = RECT::default();
+    let hit = match (hit, chrome.custom_frame.get()) {
+        (Some(hit), _) => hit,
+        (None, false) => return EventHandled::NotHandled,
+        (None, true) if unsafe { IsZoomed(event.hwnd) }.as_bool() => HitTest::Client,
+        (None, true) => match unsafe { GetClientRect(event.hwnd, &mut rect) } {
+            Ok(()) => edge_hit_test(point, &rect, frame_thickness().0),
+            Err(_) => HitTest::Client,
+        },
+    };
+    EventHandled::Handled(LRESULT(hit.code()))
+}
+
+/// Handles `WM_NCCALCSIZE` for a custom frame, making the whole
+/// window client area. A maximized window hangs over the edges of
+/// the monitor by the frame's thickness, so that's trimmed off.
+pub(crate) fn calc_size(chrome: &Chrome, event: &Event) -> EventHandled {
+    if !chrome.custom_frame.get() || event.wparam.0 == 0 {
+        return EventHandled::NotHandled;
+    }
+    if unsafe { IsZoomed(event.hwnd) }.as_bool() {
+        let params = unsafe { &mut *(event.lparam.0 as *mut NCCALCSIZE_PARAMS) };
+        let (x, y) = frame_thickness();
+        let rect = &mut params.rgrc[0];
+        rect.left += x;
+        rect.top += y;
+        rect.right -= x;
+        rect.bottom -= y;
+    }
+    EventHandled::Handled(LRESULT(0))
+}
+
+/// Handles `WM_GETMINMAXINFO`, applying the window's size limits
+pub(crate) fn min_max_info(chrome: &Chrome, event: &Event) -> EventHandled {
+    let (min_size, max_size) = (chrome.min_size.get(), chrome.max_size.get());
+    if min_size.is_none() && max_size.is_none() {
+        return EventHandled::NotHandled;
+    }
+    let info = unsafe { &mut *(event.lparam.0 as *mut MINMAXINFO) };
+    if let Some((width, height)) = min_size {
+        info.ptMinTrackSize = POINT {
+            x: width,
+            y: height,
+        };
+    }
+    if let Some((width, height)) = max_size {
+        info.ptMaxTrackSize = POINT {
+            x: width,
+            y: height,
+        };
+    }
+    EventHandled::Handled(LRESULT(0))
+}
+
+/// Handles clicks on the caption buttons of a custom frame, which
+/// would otherwise draw the standard buttons over the window
+pub(crate) fn button_click<W: Win>(win: &mut W, event: &Event) -> EventHandled {
+    if !win.get_base().chrome.custom_frame.get() {
+        return EventHandled::NotHandled;
+    }
+    let hit = event.wparam.0 as u32;
+    if hit != HTMINBUTTON && hit != HTMAXBUTTON && hit != HTCLOSE {
+        return EventHandled::NotHandled;
+    }
+    if event.message == WM_NCLBUTTONUP {
+        match hit {
+            HTMINBUTTON => {
+                win.minimize();
+            }
+            HTMAXBUTTON if win.is_maximized() => {
+                win.restore();
+            }
+            HTMAXBUTTON => {
+                win.maximize();
+            }
+            _ => unsafe {
+                // goes through on_close_requested
+                let _ = PostMessageW(event.hwnd, WM_CLOSE, WPARAM(0), LPARAM(0));
+            },
+        }
+    }
+    EventHandled::Handled(LRESULT(0))
+}
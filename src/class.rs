@@ -0,0 +1,219 @@
+//! Registers window classes on demand.
+//!
+//! Each distinct class is registered once and reference counted by
+//! the windows using it, then unregistered once the last of them has
+//! been destroyed. Unless `WinCreateArgs::class_name` is set, every
+//! `Win` type gets a class of its own with a generated name. A class
+//! takes its icon, cursor and menu from the first window created
+//! with it.
+
+use std::{
+    any::{type_name, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    mem,
+};
+use windows::{
+    core::*,
+    Win32::{Foundation::*, Graphics::Gdi::*, UI::WindowsAndMessaging::*},
+};
+
+use super::{
+    cursor::Cursor,
+    error::{Error, Result},
+    load_icon,
+    win::Win,
+    win_create_args::WinCreateArgs,
+};
+
+struct Class {
+    atom: u16,
+    instance: HINSTANCE,
+    type_id: TypeId,
+    windows: usize,
+    // the class's cursor mustn't be destroyed while it's registered
+    _cursor: Option<Cursor>,
+}
+
+#[derive(Default)]
+struct Registry {
+    classes: HashMap<String, Class>,
+    generated: HashMap<TypeId, String>,
+    // classes without windows that couldn't be unregistered yet
+    unused: Vec<String>,
+}
+
+thread_local! {
+    static REGISTRY: RefCell<Registry> = RefCell::new(Registry::default());
+}
+
+/// A window's hold on its class, released when dropped
+pub(crate) struct ClassRef {
+    key: String,
+    atom: u16,
+}
+
+impl ClassRef {
+    /// The class as passed to `CreateWindowExW`
+    pub fn name(&self) -> PCWSTR {
+        PCWSTR(self.atom as usize as *const u16)
+    }
+}
+
+impl Drop for ClassRef {
+    fn drop(&mut self) {
+        REGISTRY.with(|registry| {
+            let mut registry = registry.borrow_mut();
+            if let Some(class) = registry.classes.get_mut(&self.key) {
+                class.windows -= 1;
+                if class.windows == 0 {
+                    registry.unused.push(self.key.clone());
+                }
+            }
+        });
+    }
+}
+
+/// Registers the class for a window of type `W` if it
+/// isn't already, and takes a reference to it
+pub(crate) fn acquire<W: Win>(
+    create_args: &WinCreateArgs,
+    instance: HINSTANCE,
+) -> Result<ClassRef> {
+    sweep();
+
+    let type_id = TypeId::of::<W>();
+    let key = match &create_args.class_name {
+        None => generated_name::<W>(),
+        Some(class_name) => class_name.clone(),
+    };
+
+    let existing = REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        let class = registry.classes.get_mut(&key)?;
+        if class.type_id != type_id {
+            return Some(Err(Error::ClassConflict(key.clone())));
+        }
+        class.windows += 1;
+        Some(Ok(class.atom))
+    });
+    let atom = match existing {
+        Some(atom) => atom?,
+        None => {
+            let atom = register::<W>(&key, create_args, instance)?;
+            REGISTRY.with(|registry| {
+                registry.borrow_mut().classes.insert(
+                    key.clone(),
+                    Class {
+                        atom,
+                        instance,
+                        type_id,
+                        windows: 1,
+                        _cursor: create_args.cursor.clone(),
+                    },
+                )
+            });
+            atom
+        }
+    };
+    Ok(ClassRef { key, atom })
+}
+
+fn register<W: Win>(key: &str, create_args: &WinCreateArgs, instance: HINSTANCE) -> Result<u16> {
+    let brush = unsafe { GetStockObject(WHITE_BRUSH) };
+    let brush = HBRUSH(brush.0);
+
+    let icon = match &create_args.icon {
+        None => load_icon(HINSTANCE::default(), IDI_APPLICATION),
+        Some(icon) => load_icon(instance, icon.as_pcwstr()),
+    };
+    let icon = match icon {
+        Err(err) => {
+            // fine if the window's given an icon from image data instead
+            match create_args.large_icon.is_some() || create_args.small_icon.is_some() {
+                true => log::debug!("{}", err),
+                false => log::warn!("{}, using the default application icon", err),
+            }
+            load_icon(HINSTANCE::default(), IDI_APPLICATION).unwrap_or_default()
+        }
+        Ok(icon) => icon,
+    };
+
+    let class_name = HSTRING::from(key);
+    let wc = WNDCLASSEXW {
+        hCursor: create_args
+            .cursor
+            .as_ref()
+            .map_or(HCURSOR::default(), Cursor::handle),
+        hIcon: icon,
+        hInstance: instance,
+        lpszClassName: PCWSTR(class_name.as_ptr()),
+        hbrBackground: brush,
+        style: CS_HREDRAW | CS_VREDRAW,
+        lpfnWndProc: Some(W::wndproc),
+        cbSize: mem::size_of::<WNDCLASSEXW>() as u32,
+        lpszMenuName: create_args
+            .menu_name
+            .as_ref()
+            .map_or(PCWSTR::null(), |menu_name| menu_name.as_pcwstr()),
+        ..Default::default()
+    };
+
+    match unsafe { RegisterClassExW(&wc) } {
+        0 => Err(Error::ClassRegistration(windows::core::Error::from_win32())),
+        atom => {
+            log::debug!("registered window class {}", key);
+            Ok(atom)
+        }
+    }
+}
+
+/// A class name unique to `W`, e.g. `WindowOfOpportunity.0.MainWindow`
+pub(crate) fn generated_name<W: Win>() -> String {
+    REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        let index = registry.generated.len();
+        registry
+            .generated
+            .entry(TypeId::of::<W>())
+            .or_insert_with(|| format!("WindowOfOpportunity.{}.{}", index, short_type_name::<W>()))
+            .clone()
+    })
+}
+
+fn short_type_name<W>() -> &'static str {
+    let name = type_name::<W>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Unregisters the classes whose windows have all been destroyed.
+/// A class can't be unregistered while its last window is still
+/// being destroyed, so this is retried until it succeeds.
+pub(crate) fn sweep() {
+    let unused = REGISTRY.with(|registry| mem::take(&mut registry.borrow_mut().unused));
+    for key in unused {
+        REGISTRY.with(|registry| {
+            let mut registry = registry.borrow_mut();
+            let Some(class) = registry.classes.get(&key) else {
+                return;
+            };
+            if class.windows > 0 {
+                return;
+            }
+            let name = PCWSTR(class.atom as usize as *const u16);
+            match unsafe { UnregisterClassW(name, class.instance) } {
+                Ok(()) => {
+                    log::debug!("unregistered window class {}", key);
+                    registry.classes.remove(&key);
+                }
+                Err(_) => registry.unused.push(key),
+            }
+        });
+    }
+}
+
+/// The number of window classes currently registered
+pub fn registered_classes() -> usize {
+    REGISTRY.with(|registry| registry.borrow().classes.len())
+}
@@ -0,0 +1,159 @@
+//! Mouse cursors.
+//!
+//! A window shows its class cursor (see
+//! [`WinCreateArgs::with_cursor`](super::win_create_args::WinCreateArgs::with_cursor))
+//! unless it's given its own with [`Win::set_cursor`](super::Win::set_cursor),
+//! or one for part of its client area with
+//! [`Win::set_cursor_regions`](super::Win::set_cursor_regions).
+
+use std::rc::Rc;
+use windows::{
+    core::PCWSTR,
+    Win32::{Foundation::*, Graphics::Gdi::*, UI::WindowsAndMessaging::*},
+};
+
+use super::{
+    bitmap, error::Result, load_cursor, lword, win::Win, win_create_args::ResourceId, Event,
+    EventHandled,
+};
+
+/// The cursors that come with Windows
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SystemCursor {
+    Arrow,
+    IBeam,
+    Wait,
+    /// The arrow with a small hourglass, for work in the background
+    AppStarting,
+    Crosshair,
+    UpArrow,
+    Hand,
+    Help,
+    No,
+    SizeAll,
+    SizeNS,
+    SizeWE,
+    SizeNWSE,
+    SizeNESW,
+    Pin,
+    Person,
+}
+
+impl SystemCursor {
+    fn id(self) -> PCWSTR {
+        match self {
+            SystemCursor::Arrow => IDC_ARROW,
+            SystemCursor::IBeam => IDC_IBEAM,
+            SystemCursor::Wait => IDC_WAIT,
+            SystemCursor::AppStarting => IDC_APPSTARTING,
+            SystemCursor::Crosshair => IDC_CROSS,
+            SystemCursor::UpArrow => IDC_UPARROW,
+            SystemCursor::Hand => IDC_HAND,
+            SystemCursor::Help => IDC_HELP,
+            SystemCursor::No => IDC_NO,
+            SystemCursor::SizeAll => IDC_SIZEALL,
+            SystemCursor::SizeNS => IDC_SIZENS,
+            SystemCursor::SizeWE => IDC_SIZEWE,
+            SystemCursor::SizeNWSE => IDC_SIZENWSE,
+            SystemCursor::SizeNESW => IDC_SIZENESW,
+            SystemCursor::Pin => IDC_PIN,
+            SystemCursor::Person => IDC_PERSON,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CursorHandle {
+    handle: HCURSOR,
+    // system cursors are shared and mustn't be destroyed
+    owned: bool,
+}
+
+impl Drop for CursorHandle {
+    fn drop(&mut self) {
+        if self.owned {
+            let _ = unsafe { DestroyCursor(self.handle) };
+        }
+    }
+}
+
+/// A cursor that can be shared between windows and regions,
+/// and is destroyed once the last clone is dropped
+#[derive(Clone, Debug)]
+pub struct Cursor(Rc<CursorHandle>);
+
+impl Cursor {
+    pub fn system(cursor: SystemCursor) -> Result<Self> {
+        let handle = load_cursor(None, cursor.id())?;
+        Ok(Cursor(Rc::new(CursorHandle {
+            handle,
+            owned: false,
+        })))
+    }
+
+    /// A cursor resource in the app's executable
+    pub fn from_resource(instance: HINSTANCE, name: impl Into<ResourceId>) -> Result<Self> {
+        let handle = load_cursor(Some(instance), name.into().as_pcwstr())?;
+        Ok(Cursor(Rc::new(CursorHandle {
+            handle,
+            owned: false,
+        })))
+    }
+
+    /// A cursor from `width` × `height` RGBA pixels, whose
+    /// hotspot is at `hot_x`, `hot_y`
+    pub fn from_rgba(rgba: &[u8], width: u32, height: u32, hot_x: u32, hot_y: u32) -> Result<Self> {
+        let icon = bitmap::create_icon(rgba, width, height, Some((hot_x, hot_y)))?;
+        Ok(Cursor(Rc::new(CursorHandle {
+            handle: HCURSOR(icon.0),
+            owned: true,
+        })))
+    }
+
+    pub fn handle(&self) -> HCURSOR {
+        self.0.handle
+    }
+}
+
+/// Shows or hides the cursor while it's over the app's windows
+pub fn set_visible(visible: bool) {
+    // ShowCursor keeps a count, shown when it's 0 or more
+    unsafe {
+        match visible {
+            true => while ShowCursor(true) < 0 {},
+            false => while ShowCursor(false) >= 0 {},
+        }
+    }
+}
+
+/// Handles `WM_SETCURSOR`, showing the cursor of the region
+/// the mouse is over, or else the window's own cursor
+pub(crate) fn set_cursor<W: Win>(win: &mut W, event: &Event) -> EventHandled {
+    // leave the frame, and any child windows, to Windows
+    if event.wparam.0 != event.hwnd.0 as usize || lword(event.lparam.0) as u32 != HTCLIENT {
+        return EventHandled::NotHandled;
+    }
+    let mut point = POINT::default();
+    unsafe {
+        let _ = GetCursorPos(&mut point);
+        let _ = ScreenToClient(event.hwnd, &mut point);
+    }
+
+    let base = win.get_base();
+    let point = base.chrome.to_window_units(event.hwnd, point);
+    let cursor = base
+        .cursor_regions
+        .iter()
+        .find(|(rect, _)| unsafe { PtInRect(rect, point) }.as_bool())
+        .map(|(_, cursor)| cursor)
+        .or(base.cursor.as_ref());
+    match cursor {
+        Some(cursor) => {
+            unsafe {
+                SetCursor(cursor.handle());
+            }
+            EventHandled::Handled(LRESULT(1))
+        }
+        None => EventHandled::NotHandled,
+    }
+}
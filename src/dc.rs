@@ -1,6 +1,22 @@
-use super::get_utf16_vec;
+use std::marker::PhantomData;
+
+use super::{
+    bitmap,
+    dpi::DEFAULT_DPI,
+    error::{Error, Result},
+    get_utf16_vec,
+};
 use windows::Win32::{Foundation::*, Graphics::Gdi::*};
 
+/// Turns the `BOOL` returned by a GDI call into a `Result`
+fn check(ok: BOOL, call: &'static str) -> Result<()> {
+    if ok.as_bool() {
+        Ok(())
+    } else {
+        Err(Error::Drawing(call))
+    }
+}
+
 pub struct DeviceContext {
     hdc: HDC,
     ps: Option<PAINTSTRUCT>,
@@ -40,8 +56,37 @@ impl DeviceContext {
         }
     }
 
-    pub fn set_pixel(&self, x: i32, y: i32, hex_color: u32) -> bool {
-        unsafe { SetPixelV(self.hdc, x, y, COLORREF(hex_color)).as_bool() }
+    /// Makes drawing coordinates logical units, which
+    /// are scaled up to device pixels at `dpi`
+    pub fn set_logical_units(&self, dpi: u32) -> Result<()> {
+        let dpi = dpi as i32;
+        let default_dpi = DEFAULT_DPI as i32;
+        unsafe {
+            SetMapMode(self.hdc, MM_ANISOTROPIC);
+            check(
+                SetWindowExtEx(self.hdc, default_dpi, default_dpi, None),
+                "SetWindowExtEx",
+            )?;
+            check(
+                SetViewportExtEx(self.hdc, dpi, dpi, None),
+                "SetViewportExtEx",
+            )
+        }
+    }
+
+    /// Moves where drawing coordinates start from, in device pixels
+    pub fn set_viewport_origin(&self, x: i32, y: i32) -> Result<()> {
+        check(
+            unsafe { SetViewportOrgEx(self.hdc, x, y, None) },
+            "SetViewportOrgEx",
+        )
+    }
+
+    pub fn set_pixel(&self, x: i32, y: i32, hex_color: u32) -> Result<()> {
+        check(
+            unsafe { SetPixelV(self.hdc, x, y, COLORREF(hex_color)) },
+            "SetPixelV",
+        )
     }
 
     pub fn set_pen_color(&self, hex_color: u32) {
@@ -73,34 +118,50 @@ impl DeviceContext {
         }
     }
 
-    pub fn move_to(&self, x: i32, y: i32) -> bool {
-        unsafe { MoveToEx(self.hdc, x, y, None).as_bool() }
+    pub fn move_to(&self, x: i32, y: i32) -> Result<()> {
+        check(unsafe { MoveToEx(self.hdc, x, y, None) }, "MoveToEx")
     }
 
-    pub fn line_to(&self, x: i32, y: i32) -> bool {
-        unsafe { LineTo(self.hdc, x, y).as_bool() }
+    pub fn line_to(&self, x: i32, y: i32) -> Result<()> {
+        check(unsafe { LineTo(self.hdc, x, y) }, "LineTo")
     }
 
-    pub fn polyline(&self, points: &[POINT]) -> bool {
-        unsafe { Polyline(self.hdc, points).as_bool() }
+    pub fn polyline(&self, points: &[POINT]) -> Result<()> {
+        check(unsafe { Polyline(self.hdc, points) }, "Polyline")
     }
 
-    pub fn rectangle(&self, l: i32, t: i32, r: i32, b: i32) -> bool {
-        unsafe { Rectangle(self.hdc, l, t, r, b).as_bool() }
+    pub fn rectangle(&self, l: i32, t: i32, r: i32, b: i32) -> Result<()> {
+        check(unsafe { Rectangle(self.hdc, l, t, r, b) }, "Rectangle")
     }
 
-    pub fn ellipse(&self, l: i32, t: i32, r: i32, b: i32) -> bool {
-        unsafe { Ellipse(self.hdc, l, t, r, b).as_bool() }
+    pub fn ellipse(&self, l: i32, t: i32, r: i32, b: i32) -> Result<()> {
+        check(unsafe { Ellipse(self.hdc, l, t, r, b) }, "Ellipse")
     }
 
-    pub fn round_rect(&self, l: i32, t: i32, r: i32, b: i32, x_corn: i32, y_corn: i32) -> bool {
-        unsafe { RoundRect(self.hdc, l, t, r, b, x_corn, y_corn).as_bool() }
+    pub fn round_rect(
+        &self,
+        l: i32,
+        t: i32,
+        r: i32,
+        b: i32,
+        x_corn: i32,
+        y_corn: i32,
+    ) -> Result<()> {
+        check(
+            unsafe { RoundRect(self.hdc, l, t, r, b, x_corn, y_corn) },
+            "RoundRect",
+        )
     }
 
-    pub fn poly_bezier(&self, points: &[POINT]) -> bool {
-        unsafe { PolyBezier(self.hdc, points).as_bool() }
+    pub fn poly_bezier(&self, points: &[POINT]) -> Result<()> {
+        check(unsafe { PolyBezier(self.hdc, points) }, "PolyBezier")
     }
 
+    /// Selects `obj` for good, leaving it selected when the device
+    /// context is released and it can no longer be deleted
+    #[deprecated(
+        note = "use `select_pen`, `select_brush` or `select_stock`, which deselect on drop"
+    )]
     pub fn select_object(&self, obj: HGDIOBJ) {
         unsafe {
             SelectObject(self.hdc, obj);
@@ -111,6 +172,39 @@ impl DeviceContext {
         unsafe { GetStockObject(i) }
     }
 
+    /// Draws with `pen` until the returned guard is dropped, which
+    /// puts back the pen that was selected before
+    pub fn select_pen<'a>(&'a self, pen: &'a Pen) -> Selection<'a> {
+        self.select(pen.handle.into())
+    }
+
+    /// Fills with `brush` until the returned guard is dropped, which
+    /// puts back the brush that was selected before
+    pub fn select_brush<'a>(&'a self, brush: &'a Brush) -> Selection<'a> {
+        self.select(brush.handle.into())
+    }
+
+    /// Selects a stock pen, brush or font until the returned guard is dropped
+    pub fn select_stock(&self, i: GET_STOCK_OBJECT_FLAGS) -> Selection<'_> {
+        self.select(self.get_stock_object(i))
+    }
+
+    fn select(&self, obj: HGDIOBJ) -> Selection<'_> {
+        let previous = unsafe { SelectObject(self.hdc, obj) };
+        Selection {
+            hdc: self.hdc,
+            previous,
+            _selected: PhantomData,
+        }
+    }
+
+    pub fn fill_rect(&self, rect: &RECT, brush: &Brush) -> Result<()> {
+        match unsafe { FillRect(self.hdc, rect, brush.handle) } {
+            0 => Err(Error::Drawing("FillRect")),
+            _ => Ok(()),
+        }
+    }
+
     pub fn text_out(&self, text: &str, x: i32, y: i32) {
         unsafe {
             TabbedTextOutW(
@@ -181,3 +275,70 @@ impl Drop for Pen {
         }
     }
 }
+
+/// A pen or brush selected into a [`DeviceContext`], which is
+/// deselected when this is dropped. The object can't be dropped
+/// while it's selected, as GDI can't delete a selected object.
+#[must_use = "the object is deselected as soon as this is dropped"]
+pub struct Selection<'a> {
+    hdc: HDC,
+    previous: HGDIOBJ,
+    _selected: PhantomData<&'a ()>,
+}
+
+impl Drop for Selection<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            SelectObject(self.hdc, self.previous);
+        }
+    }
+}
+
+pub struct Brush {
+    pub handle: HBRUSH,
+}
+
+impl Brush {
+    pub fn solid(hex_color: u32) -> Result<Self> {
+        let handle = unsafe { CreateSolidBrush(COLORREF(hex_color)) };
+        Self::checked(handle, "CreateSolidBrush")
+    }
+
+    pub fn hatched(style: HATCH_BRUSH_STYLE, hex_color: u32) -> Result<Self> {
+        let handle = unsafe { CreateHatchBrush(style, COLORREF(hex_color)) };
+        Self::checked(handle, "CreateHatchBrush")
+    }
+
+    /// A brush tiling `bitmap`, which is copied so can be
+    /// deleted once the brush has been made
+    pub fn pattern(bitmap: HBITMAP) -> Result<Self> {
+        let handle = unsafe { CreatePatternBrush(bitmap) };
+        Self::checked(handle, "CreatePatternBrush")
+    }
+
+    /// A brush tiling `width` × `height` RGBA pixels
+    pub fn from_rgba(rgba: &[u8], width: u32, height: u32) -> Result<Self> {
+        bitmap::check_rgba(rgba, width, height)?;
+        let dib = bitmap::create_dib(width, height, &bitmap::rgba_to_bgra(rgba, false))?;
+        let brush = Self::pattern(dib);
+        unsafe {
+            let _ = DeleteObject(dib);
+        }
+        brush
+    }
+
+    fn checked(handle: HBRUSH, call: &'static str) -> Result<Self> {
+        match handle.is_invalid() {
+            true => Err(Error::Drawing(call)),
+            false => Ok(Self { handle }),
+        }
+    }
+}
+
+impl Drop for Brush {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = DeleteObject(self.handle);
+        }
+    }
+}
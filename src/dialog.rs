@@ -0,0 +1,261 @@
+use std::cell::RefCell;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        UI::{Input::KeyboardAndMouse::EnableWindow, WindowsAndMessaging::*},
+    },
+};
+
+use super::{
+    error::{Error, Result},
+    executor, get_utf16_vec,
+    handle::WinHandle,
+    win::Win,
+    win_create_args::WinCreateArgs,
+    WPModule, WM_EXECUTOR_WAKE,
+};
+
+thread_local! {
+    // (dialog, owner) for every modal dialog that's running
+    static MODAL_OWNERS: RefCell<Vec<(isize, isize)>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageBoxIcon {
+    None,
+    Info,
+    Warning,
+    Error,
+    Question,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageBoxButtons {
+    Ok,
+    OkCancel,
+    YesNo,
+    YesNoCancel,
+    RetryCancel,
+    AbortRetryIgnore,
+    CancelTryContinue,
+}
+
+/// The button the user chose to close a message box with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageBoxResult {
+    Ok,
+    Cancel,
+    Yes,
+    No,
+    Retry,
+    Abort,
+    Ignore,
+    TryAgain,
+    Continue,
+}
+
+/// Shows a message box and waits for the user to close it. With an
+/// `owner` the message box is modal to that window.
+pub fn message_box(
+    owner: Option<HWND>,
+    text: &str,
+    caption: &str,
+    icon: MessageBoxIcon,
+    buttons: MessageBoxButtons,
+) -> MessageBoxResult {
+    let icon = match icon {
+        MessageBoxIcon::None => MESSAGEBOX_STYLE(0),
+        MessageBoxIcon::Info => MB_ICONINFORMATION,
+        MessageBoxIcon::Warning => MB_ICONWARNING,
+        MessageBoxIcon::Error => MB_ICONERROR,
+        MessageBoxIcon::Question => MB_ICONQUESTION,
+    };
+    let buttons = match buttons {
+        MessageBoxButtons::Ok => MB_OK,
+        MessageBoxButtons::OkCancel => MB_OKCANCEL,
+        MessageBoxButtons::YesNo => MB_YESNO,
+        MessageBoxButtons::YesNoCancel => MB_YESNOCANCEL,
+        MessageBoxButtons::RetryCancel => MB_RETRYCANCEL,
+        MessageBoxButtons::AbortRetryIgnore => MB_ABORTRETRYIGNORE,
+        MessageBoxButtons::CancelTryContinue => MB_CANCELTRYCONTINUE,
+    };
+
+    let text = get_utf16_vec(text);
+    let caption = get_utf16_vec(caption);
+    let result = unsafe {
+        MessageBoxW(
+            owner.unwrap_or_default(),
+            PCWSTR(text.as_ptr()),
+            PCWSTR(caption.as_ptr()),
+            icon | buttons,
+        )
+    };
+
+    match result {
+        IDOK => MessageBoxResult::Ok,
+        IDYES => MessageBoxResult::Yes,
+        IDNO => MessageBoxResult::No,
+        IDRETRY => MessageBoxResult::Retry,
+        IDABORT => MessageBoxResult::Abort,
+        IDIGNORE => MessageBoxResult::Ignore,
+        IDTRYAGAIN => MessageBoxResult::TryAgain,
+        IDCONTINUE => MessageBoxResult::Continue,
+        _ => MessageBoxResult::Cancel,
+    }
+}
+
+pub fn info(owner: Option<HWND>, text: &str, caption: &str) {
+    message_box(
+        owner,
+        text,
+        caption,
+        MessageBoxIcon::Info,
+        MessageBoxButtons::Ok,
+    );
+}
+
+pub fn warning(owner: Option<HWND>, text: &str, caption: &str) {
+    message_box(
+        owner,
+        text,
+        caption,
+        MessageBoxIcon::Warning,
+        MessageBoxButtons::Ok,
+    );
+}
+
+pub fn error(owner: Option<HWND>, text: &str, caption: &str) {
+    message_box(
+        owner,
+        text,
+        caption,
+        MessageBoxIcon::Error,
+        MessageBoxButtons::Ok,
+    );
+}
+
+/// A window that can be run modally with [`run_modal`]
+pub trait Dialog: Win {
+    type Output;
+
+    /// Called once the dialog has been destroyed, to
+    /// collect whatever the user chose
+    fn take_result(&mut self) -> Option<Self::Output>;
+}
+
+/// Creates a `D` owned by `owner` and runs it modally: the owner is
+/// disabled and this doesn't return until the dialog is destroyed.
+/// The dialog is centred over its owner.
+pub fn run_modal<D: Dialog>(
+    owner: HWND,
+    title: &str,
+    create_args: &WinCreateArgs,
+) -> Result<Option<D::Output>> {
+    let create_args = WinCreateArgs {
+        parent: Some(owner),
+        ..create_args.clone()
+    };
+    let dialog = WinHandle::new(D::new(WPModule::new().get_hinstance()));
+    let hwnd = dialog.create_window_with_args(title, &create_args)?;
+    center_over(hwnd, owner);
+
+    // an owner that's already disabled belongs to an outer modal,
+    // which re-enables it itself
+    let already_disabled = unsafe { EnableWindow(owner, false) }.as_bool();
+    if !already_disabled {
+        MODAL_OWNERS.with(|owners| {
+            owners
+                .borrow_mut()
+                .push((hwnd.0 as isize, owner.0 as isize))
+        });
+    }
+    dialog.show();
+    dialog.update();
+
+    let mut msg = MSG::default();
+    let mut failed = None;
+    while unsafe { IsWindow(hwnd) }.as_bool() {
+        match unsafe { GetMessageW(&mut msg, None, 0, 0) }.0 {
+            -1 => {
+                failed = Some(windows::core::Error::from_win32());
+                unsafe {
+                    closing(hwnd);
+                    let _ = DestroyWindow(hwnd);
+                }
+                break;
+            }
+            0 => {
+                // leave the quit for the app's own message loop
+                unsafe {
+                    PostQuitMessage(msg.wParam.0 as i32);
+                    closing(hwnd);
+                    let _ = DestroyWindow(hwnd);
+                }
+                break;
+            }
+            _ => {}
+        }
+        if msg.message == WM_EXECUTOR_WAKE {
+            executor::run_ready();
+            continue;
+        }
+        unsafe {
+            if !IsDialogMessageW(hwnd, &msg).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+
+    // in case the dialog was destroyed without being closed
+    closing(hwnd);
+    unsafe {
+        let _ = SetForegroundWindow(owner);
+    }
+    if let Some(err) = failed {
+        return Err(Error::Backend(err));
+    }
+    let result = dialog.borrow_mut().take_result();
+    Ok(result)
+}
+
+/// Re-enables a modal dialog's owner as the dialog is closed. This
+/// has to come before the dialog is destroyed, or Windows activates
+/// another app's window in place of the still disabled owner.
+pub(crate) fn closing(hwnd: HWND) {
+    let owner = MODAL_OWNERS.with(|owners| {
+        let mut owners = owners.borrow_mut();
+        let index = owners
+            .iter()
+            .position(|(dialog, _)| *dialog == hwnd.0 as isize)?;
+        Some(owners.remove(index).1)
+    });
+    if let Some(owner) = owner {
+        unsafe {
+            let _ = EnableWindow(HWND(owner as _), true);
+        }
+    }
+}
+
+fn center_over(hwnd: HWND, owner: HWND) {
+    let mut rect = RECT::default();
+    let mut owner_rect = RECT::default();
+    unsafe {
+        if GetWindowRect(hwnd, &mut rect).is_err() || GetWindowRect(owner, &mut owner_rect).is_err()
+        {
+            return;
+        }
+        let width = rect.right - rect.left;
+        let height = rect.bottom - rect.top;
+        let _ = SetWindowPos(
+            hwnd,
+            None,
+            owner_rect.left + (owner_rect.right - owner_rect.left - width) / 2,
+            owner_rect.top + (owner_rect.bottom - owner_rect.top - height) / 2,
+            0,
+            0,
+            SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE,
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Per-monitor DPI awareness.
+//!
+//! By default Windows draws the app at 96 DPI and stretches its
+//! windows to fit, so they look blurry on high DPI monitors. An app
+//! that calls [`enable_per_monitor_awareness`] before creating any
+//! windows draws at each monitor's real DPI instead, and its windows
+//! are told through [`Win::on_dpi_changed`](super::Win::on_dpi_changed)
+//! when they move to a monitor with a different one.
+//!
+//! A DPI aware window can ask for its drawing and input to be in
+//! logical units, which are pixels at 96 DPI, with
+//! [`Win::set_logical_units`](super::Win::set_logical_units).
+
+use windows::Win32::{
+    Foundation::*,
+    UI::{HiDpi::*, WindowsAndMessaging::*},
+};
+
+use super::{error::Result, win::Win, Event, EventHandled};
+
+/// The DPI Windows treats as a scale factor of `1.0`
+pub const DEFAULT_DPI: u32 = USER_DEFAULT_SCREEN_DPI;
+
+/// Makes the app draw at each monitor's own DPI. It has to be
+/// called before any windows are created, and fails if the
+/// app's manifest already sets the DPI awareness.
+pub fn enable_per_monitor_awareness() -> Result<()> {
+    unsafe {
+        SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2)?;
+    }
+    Ok(())
+}
+
+/// How much bigger than at 96 DPI things are drawn, e.g. `1.5` at 144 DPI
+pub fn scale_factor(dpi: u32) -> f32 {
+    dpi as f32 / DEFAULT_DPI as f32
+}
+
+/// Converts device pixels at `dpi` to logical units
+pub fn to_logical(pixels: i32, dpi: u32) -> i32 {
+    scale(pixels, DEFAULT_DPI, dpi)
+}
+
+/// Converts logical units to device pixels at `dpi`
+pub fn to_physical(units: i32, dpi: u32) -> i32 {
+    scale(units, dpi, DEFAULT_DPI)
+}
+
+// `value * numerator / denominator`, rounded to the nearest
+fn scale(value: i32, numerator: u32, denominator: u32) -> i32 {
+    let (value, numerator, denominator) = (value as i64, numerator as i64, denominator as i64);
+    let product = value * numerator;
+    let rounded = match product < 0 {
+        true => product - denominator / 2,
+        false => product + denominator / 2,
+    };
+    (rounded / denominator) as i32
+}
+
+pub(crate) fn window_dpi(hwnd: HWND) -> u32 {
+    match unsafe { GetDpiForWindow(hwnd) } {
+        0 => DEFAULT_DPI,
+        dpi => dpi,
+    }
+}
+
+/// Handles `WM_DPICHANGED`, moving the window to the suggested
+/// rect unless `on_dpi_changed` has placed it itself
+pub(crate) fn dpi_changed<W: Win>(win: &mut W, event: &Event) -> EventHandled {
+    let dpi = event.wparam.0 as u32 & 0xFFFF;
+    // replayed after arriving while the window was borrowed, when
+    // it was moved straight away, see `move_to_suggested`
+    if event.lparam.0 == 0 {
+        let current = win.get_window_rect().unwrap_or_default();
+        win.on_dpi_changed(dpi, &current);
+        return EventHandled::Handled(LRESULT(0));
+    }
+    let suggested = unsafe { *(event.lparam.0 as *const RECT) };
+    if let EventHandled::Handled(result) = win.on_dpi_changed(dpi, &suggested) {
+        return EventHandled::Handled(result);
+    }
+    move_to_suggested(event.hwnd, &suggested);
+    EventHandled::Handled(LRESULT(0))
+}
+
+/// Moves the window to the rect suggested by `WM_DPICHANGED`
+pub(crate) fn move_to_suggested(hwnd: HWND, suggested: &RECT) {
+    unsafe {
+        let _ = SetWindowPos(
+            hwnd,
+            None,
+            suggested.left,
+            suggested.top,
+            suggested.right - suggested.left,
+            suggested.bottom - suggested.top,
+            SWP_NOZORDER | SWP_NOACTIVATE,
+        );
+    }
+}
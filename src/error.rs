@@ -0,0 +1,116 @@
+use std::{cell::RefCell, fmt};
+
+/// Everything that can go wrong in `window_of_opportunity`
+#[derive(Debug)]
+pub enum Error {
+    /// `CreateWindowExW` failed for a window or control
+    WindowCreation(windows::core::Error),
+    /// The window class couldn't be registered
+    ClassRegistration(windows::core::Error),
+    /// The window class is already registered for another `Win` type
+    ClassConflict(String),
+    /// An icon, cursor, menu or accelerator table couldn't be loaded
+    ResourceLoad {
+        name: String,
+        source: windows::core::Error,
+    },
+    /// `WinCreateArgs::validate` found a conflicting combination
+    InvalidCreateArgs(&'static str),
+    /// An image buffer passed in doesn't match its dimensions
+    /// or couldn't be decoded
+    InvalidImage(&'static str),
+    /// A GDI drawing call failed, named by the call
+    Drawing(&'static str),
+    /// The target window has already been destroyed
+    WindowClosed,
+    /// Every id for the named kind of item is in use
+    IdsExhausted(&'static str),
+    /// Two menu items were given the same id
+    DuplicateMenuId(u16),
+    /// Any other failure reported by the Win32 API
+    Backend(windows::core::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::WindowCreation(err) => write!(f, "couldn't create window: {}", err),
+            Error::ClassRegistration(err) => {
+                write!(f, "couldn't register window class: {}", err)
+            }
+            Error::ClassConflict(name) => {
+                write!(f, "window class {} belongs to another window type", name)
+            }
+            Error::ResourceLoad { name, source } => {
+                write!(f, "couldn't load resource {}: {}", name, source)
+            }
+            Error::InvalidCreateArgs(reason) => write!(f, "invalid create args: {}", reason),
+            Error::InvalidImage(reason) => write!(f, "invalid image: {}", reason),
+            Error::Drawing(call) => write!(f, "{} failed", call),
+            Error::WindowClosed => write!(f, "the window has been destroyed"),
+            Error::IdsExhausted(kind) => write!(f, "there are too many {}", kind),
+            Error::DuplicateMenuId(id) => write!(f, "menu item id {} is used twice", id),
+            Error::Backend(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::WindowCreation(err)
+            | Error::ClassRegistration(err)
+            | Error::ResourceLoad { source: err, .. }
+            | Error::Backend(err) => Some(err),
+            Error::ClassConflict(_)
+            | Error::InvalidCreateArgs(_)
+            | Error::InvalidImage(_)
+            | Error::Drawing(_)
+            | Error::WindowClosed
+            | Error::IdsExhausted(_)
+            | Error::DuplicateMenuId(_) => None,
+        }
+    }
+}
+
+impl From<windows::core::Error> for Error {
+    fn from(err: windows::core::Error) -> Self {
+        Error::Backend(err)
+    }
+}
+
+type ErrorHook = Box<dyn FnMut(&Error)>;
+
+thread_local! {
+    static ERROR_HOOK: RefCell<Option<ErrorHook>> = RefCell::new(None);
+}
+
+/// Sets the hook that errors from inside message handlers are
+/// passed to. Without one they are logged at `error` level.
+pub fn set_error_hook<F>(hook: F)
+where
+    F: FnMut(&Error) + 'static,
+{
+    ERROR_HOOK.with(|cell| *cell.borrow_mut() = Some(Box::new(hook)));
+}
+
+/// Reports an error that has nowhere to be returned to,
+/// e.g. one raised while handling a message
+pub fn report(err: Error) {
+    // taken out while it runs, in case the hook reports errors itself
+    let hook = ERROR_HOOK.with(|cell| cell.borrow_mut().take());
+    match hook {
+        None => log::error!("{}", err),
+        Some(mut hook) => {
+            hook(&err);
+            ERROR_HOOK.with(|cell| {
+                let mut cell = cell.borrow_mut();
+                if cell.is_none() {
+                    *cell = Some(hook);
+                }
+            });
+        }
+    }
+}
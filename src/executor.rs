@@ -0,0 +1,199 @@
+//! A single threaded executor that runs futures on the UI thread.
+//!
+//! Futures are polled from the message loop. When a future's waker
+//! fires (from any thread) it posts a message to the app's main window,
+//! so the executor only does work when there is something to poll and
+//! the message loop can otherwise sit in `GetMessage`.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicIsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    task::{Context, Wake, Waker},
+};
+use windows::Win32::{
+    Foundation::*, System::Threading::GetCurrentThreadId, UI::WindowsAndMessaging::*,
+};
+
+use super::{win::Win, WinCallback, WM_EXECUTOR_UPDATE, WM_EXECUTOR_WAKE};
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+/// The part of the executor shared with wakers, which
+/// may be sent to and woken from other threads
+struct ReadyQueue {
+    ready: Mutex<VecDeque<usize>>,
+    wake_pending: AtomicBool,
+    hwnd: AtomicIsize,
+    thread_id: u32,
+}
+
+impl ReadyQueue {
+    fn schedule(&self, id: usize) {
+        self.ready
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push_back(id);
+
+        if self.wake_pending.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let hwnd = HWND(self.hwnd.load(Ordering::Acquire) as _);
+        unsafe {
+            if hwnd.is_invalid()
+                || PostMessageW(hwnd, WM_EXECUTOR_WAKE, WPARAM(0), LPARAM(0)).is_err()
+            {
+                let _ = PostThreadMessageW(self.thread_id, WM_EXECUTOR_WAKE, WPARAM(0), LPARAM(0));
+            }
+        }
+    }
+
+    fn take_ready(&self) -> VecDeque<usize> {
+        self.wake_pending.store(false, Ordering::Release);
+        std::mem::take(&mut *self.ready.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+struct TaskWaker {
+    id: usize,
+    queue: Arc<ReadyQueue>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.queue.schedule(self.id);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.queue.schedule(self.id);
+    }
+}
+
+struct Executor {
+    tasks: RefCell<HashMap<usize, Task>>,
+    next_id: Cell<usize>,
+    queue: Arc<ReadyQueue>,
+    updates: RefCell<Vec<(isize, WinCallback)>>,
+}
+
+impl Executor {
+    fn new() -> Self {
+        Executor {
+            tasks: RefCell::new(HashMap::new()),
+            next_id: Cell::new(0),
+            queue: Arc::new(ReadyQueue {
+                ready: Mutex::new(VecDeque::new()),
+                wake_pending: AtomicBool::new(false),
+                hwnd: AtomicIsize::new(0),
+                thread_id: unsafe { GetCurrentThreadId() },
+            }),
+            updates: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+thread_local! {
+    static EXECUTOR: Executor = Executor::new();
+}
+
+/// Spawns a future on the current (UI) thread's executor. The
+/// future is first polled the next time the message loop runs.
+pub fn spawn_local<F>(future: F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    EXECUTOR.with(|executor| {
+        let id = executor.next_id.get();
+        executor.next_id.set(id + 1);
+        executor.tasks.borrow_mut().insert(id, Box::pin(future));
+        executor.queue.schedule(id);
+    });
+}
+
+/// Sets the window that wakers post to. Until this is
+/// called wake messages are posted to the thread instead.
+pub(crate) fn set_wake_window(hwnd: HWND) {
+    EXECUTOR.with(|executor| {
+        executor
+            .queue
+            .hwnd
+            .store(hwnd.0 as isize, Ordering::Release);
+    });
+}
+
+/// Polls every task that has been woken since the last call.
+/// Tasks woken while this runs are left for the next wake message
+/// so a busy future can't starve the message loop.
+pub(crate) fn run_ready() {
+    EXECUTOR.with(|executor| {
+        for id in executor.queue.take_ready() {
+            // the task may have finished, or be further up the
+            // stack if it's running a nested message loop
+            let task = executor.tasks.borrow_mut().remove(&id);
+            let Some(mut task) = task else {
+                continue;
+            };
+
+            let waker = Waker::from(Arc::new(TaskWaker {
+                id,
+                queue: executor.queue.clone(),
+            }));
+            if task
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_pending()
+            {
+                executor.tasks.borrow_mut().insert(id, task);
+            }
+        }
+    });
+}
+
+/// Queues `update` to be run against the window `hwnd` the next
+/// time it processes messages, dropping it if the window has gone
+pub(crate) fn post_update(hwnd: HWND, update: WinCallback) {
+    if hwnd.is_invalid() {
+        return;
+    }
+    EXECUTOR.with(|executor| {
+        executor
+            .updates
+            .borrow_mut()
+            .push((hwnd.0 as isize, update));
+    });
+    unsafe {
+        if PostMessageW(hwnd, WM_EXECUTOR_UPDATE, WPARAM(0), LPARAM(0)).is_err() {
+            discard_updates(hwnd);
+        }
+    }
+}
+
+fn take_updates(hwnd: HWND) -> Vec<WinCallback> {
+    let hwnd = hwnd.0 as isize;
+    EXECUTOR.with(|executor| {
+        let mut updates = executor.updates.borrow_mut();
+        let (mine, others): (Vec<_>, Vec<_>) = std::mem::take(&mut *updates)
+            .into_iter()
+            .partition(|(target, _)| *target == hwnd);
+        *updates = others;
+        mine.into_iter().map(|(_, update)| update).collect()
+    })
+}
+
+/// Runs the queued updates for `win`
+pub(crate) fn apply_updates<W: Win>(win: &mut W) {
+    for mut update in take_updates(win.get_hwnd()) {
+        update(win);
+    }
+}
+
+/// Drops any queued updates for a window that is being destroyed
+pub(crate) fn discard_updates(hwnd: HWND) {
+    // dropped outside the borrow, in case a captured value's drop spawns
+    drop(take_updates(hwnd));
+}
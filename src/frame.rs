@@ -0,0 +1,168 @@
+use std::{
+    collections::VecDeque,
+    mem,
+    time::{Duration, Instant},
+};
+
+/// The most fixed steps run in one go when catching up, so a
+/// slow frame can't snowball into ever longer catch-ups
+const MAX_CATCH_UP_STEPS: u32 = 5;
+
+/// The number of recent frames the statistics are taken over
+const STATS_WINDOW: usize = 120;
+
+/// How `WPApp::run` paces calls to [`Win::on_frame`] when running
+/// as a game loop
+///
+/// [`Win::on_frame`]: crate::Win::on_frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameTiming {
+    /// Run a frame up to this many times a second, passing the
+    /// real time since the last frame. Zero runs frames back to
+    /// back without waiting.
+    TargetFps(u32),
+    /// Run frames with this fixed delta, as many as are
+    /// needed to keep up with real time
+    FixedStep(Duration),
+}
+
+impl FrameTiming {
+    fn interval(&self) -> Duration {
+        match *self {
+            FrameTiming::TargetFps(0) => Duration::ZERO,
+            FrameTiming::TargetFps(fps) => Duration::from_secs(1) / fps,
+            FrameTiming::FixedStep(step) => step,
+        }
+    }
+}
+
+/// Decides when frames are due and what delta they are run with
+pub(crate) struct FrameClock {
+    timing: FrameTiming,
+    last_tick: Instant,
+    next_frame: Instant,
+    accumulator: Duration,
+    // real time not yet recorded in the stats, as a fixed step
+    // tick may run no frames
+    unrecorded: Duration,
+    ticked: bool,
+}
+
+impl FrameClock {
+    pub fn new(timing: FrameTiming, now: Instant) -> Self {
+        Self {
+            timing,
+            last_tick: now,
+            next_frame: now,
+            accumulator: Duration::ZERO,
+            unrecorded: Duration::ZERO,
+            ticked: false,
+        }
+    }
+
+    /// How long to wait for the next frame, or `None` if it's due
+    pub fn time_until_next(&self, now: Instant) -> Option<Duration> {
+        match self.next_frame.checked_duration_since(now) {
+            Some(wait) if !wait.is_zero() => Some(wait),
+            _ => None,
+        }
+    }
+
+    /// Advances the clock to `now`, returning the deltas to run
+    /// `on_frame` with (which may be none for a fixed step) and
+    /// recording a frame time for each. The first tick has no
+    /// previous frame, so isn't recorded.
+    pub fn tick(&mut self, now: Instant, stats: &mut FrameStats) -> Vec<Duration> {
+        let elapsed = now.duration_since(self.last_tick);
+        self.last_tick = now;
+
+        let deltas = self.deltas(now, elapsed);
+        if !mem::replace(&mut self.ticked, true) {
+            return deltas;
+        }
+        self.unrecorded += elapsed;
+        if !deltas.is_empty() {
+            // catch-up frames share the real time between them
+            let frame_time = self.unrecorded / deltas.len() as u32;
+            for _ in &deltas {
+                stats.record(frame_time);
+            }
+            self.unrecorded = Duration::ZERO;
+        }
+        deltas
+    }
+
+    fn deltas(&mut self, now: Instant, elapsed: Duration) -> Vec<Duration> {
+        let interval = self.timing.interval();
+        self.next_frame += interval;
+        if self.next_frame <= now {
+            // fallen behind, so don't try to make up the lost frames
+            self.next_frame = now + interval;
+        }
+
+        match self.timing {
+            FrameTiming::TargetFps(_) => vec![elapsed],
+            FrameTiming::FixedStep(step) if step.is_zero() => vec![elapsed],
+            FrameTiming::FixedStep(step) => {
+                self.accumulator += elapsed;
+                let steps = (self.accumulator.as_nanos() / step.as_nanos())
+                    .min(u128::from(MAX_CATCH_UP_STEPS) + 1) as u32;
+                self.accumulator -= step * steps;
+                if steps > MAX_CATCH_UP_STEPS {
+                    self.accumulator = Duration::ZERO;
+                }
+                vec![step; steps.min(MAX_CATCH_UP_STEPS) as usize]
+            }
+        }
+    }
+}
+
+/// Frame-time statistics for the game loop, taken over
+/// the most recent frames
+#[derive(Debug, Default)]
+pub struct FrameStats {
+    frame_count: u64,
+    recent: VecDeque<Duration>,
+}
+
+impl FrameStats {
+    pub(crate) fn record(&mut self, frame_time: Duration) {
+        self.frame_count += 1;
+        if self.recent.len() == STATS_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(frame_time);
+    }
+
+    /// The number of frames run since the loop started
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn last_frame_time(&self) -> Duration {
+        self.recent.back().copied().unwrap_or_default()
+    }
+
+    pub fn average_frame_time(&self) -> Duration {
+        match self.recent.len() {
+            0 => Duration::ZERO,
+            len => self.recent.iter().sum::<Duration>() / len as u32,
+        }
+    }
+
+    pub fn min_frame_time(&self) -> Duration {
+        self.recent.iter().min().copied().unwrap_or_default()
+    }
+
+    pub fn max_frame_time(&self) -> Duration {
+        self.recent.iter().max().copied().unwrap_or_default()
+    }
+
+    /// Frames per second, from the average frame time
+    pub fn fps(&self) -> f64 {
+        match self.average_frame_time().as_secs_f64() {
+            secs if secs > 0.0 => 1.0 / secs,
+            _ => 0.0,
+        }
+    }
+}
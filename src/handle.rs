@@ -0,0 +1,439 @@
+//! Ownership of the state behind a window's wndproc.
+//!
+//! A window's state lives in a reference counted cell shared by
+//! every [`WinHandle`] to it and by the native window itself, which
+//! holds its own strong reference from `WM_NCCREATE` until
+//! `WM_NCDESTROY`. The state can't move or be dropped while the
+//! native window exists, so the pointer the wndproc uses never
+//! dangles.
+//!
+//! Messages are handled through a `RefCell` borrow of the state. A
+//! message that arrives while the window is already borrowed (e.g. a
+//! handler calls `SetWindowPos` or shows a message box) gets the
+//! default handling there and then, and notifications whose result
+//! doesn't matter, like `WM_SIZE`, `WM_TIMER` or `WM_DESTROY`, are
+//! replayed to the window once the borrow ends. A `WM_PAINT` is
+//! validated by the default handling, so the window is invalidated
+//! again once the borrow ends and repaints then. The frame, size
+//! limit and hit test queries are answered from state the window
+//! shares with its cell, so they don't fall back to the defaults.
+
+use std::{
+    cell::{Cell, Ref, RefCell, RefMut},
+    collections::VecDeque,
+    ops::{Deref, DerefMut},
+    rc::Rc,
+};
+use windows::{
+    core::*,
+    Win32::{Foundation::*, Graphics::Gdi::*, UI::WindowsAndMessaging::*},
+};
+
+use super::{
+    chrome::{self, Chrome},
+    class::{self, ClassRef},
+    dpi,
+    error::{self, Error, Result},
+    icon::IconSize,
+    mdi, placement, trace,
+    win::Win,
+    win_create_args::WinCreateArgs,
+    Event, EventHandled, WPModule, WM_EXECUTOR_UPDATE, WM_EXECUTOR_WAKE, WM_TRAY, WM_USER_EVENT,
+};
+
+pub(crate) struct WinCell<W> {
+    win: RefCell<W>,
+    hwnd: Cell<HWND>,
+    show_cmd: Cell<SHOW_WINDOW_CMD>,
+    class: RefCell<Option<ClassRef>>,
+    deferred: RefCell<VecDeque<Event>>,
+    // a paint arrived while borrowed, and was validated without painting
+    repaint: Cell<bool>,
+    chrome: Rc<Chrome>,
+}
+
+impl<W: Win> WinCell<W> {
+    /// Gives a message that arrived while the window was
+    /// borrowed the default handling, queueing it to be
+    /// replayed if it's a notification
+    fn defer(&self, mut event: Event) -> LRESULT {
+        let result = match event.message {
+            // the close may still be vetoed, so leave it to the replay
+            WM_CLOSE => LRESULT(0),
+            // the suggested rect doesn't outlive the message, so the
+            // window's moved now and the replay only passes on the DPI
+            WM_DPICHANGED => {
+                dpi::move_to_suggested(event.hwnd, unsafe { &*(event.lparam.0 as *const RECT) });
+                event.lparam = LPARAM(0);
+                LRESULT(0)
+            }
+            _ => match chrome::answer_borrowed(&self.chrome, &event) {
+                EventHandled::Handled(result) => {
+                    mdi::handled(event.hwnd, event.message, event.wparam, event.lparam);
+                    result
+                }
+                EventHandled::NotHandled => {
+                    mdi::default_proc(event.hwnd, event.message, event.wparam, event.lparam)
+                }
+            },
+        };
+
+        if event.message == WM_PAINT {
+            self.repaint.set(true);
+            return result;
+        }
+        if !is_notification(event.message) {
+            log::trace!(
+                "{} arrived while the window was borrowed",
+                trace::message_name(event.message)
+            );
+            return result;
+        }
+        self.deferred.borrow_mut().push_back(event);
+        result
+    }
+
+    /// Replays the notifications that arrived while the window
+    /// was borrowed, unless it's still borrowed further up, and
+    /// invalidates it again if a paint was missed
+    fn replay_deferred(&self) {
+        loop {
+            if self.deferred.borrow().is_empty() && !self.repaint.get() {
+                return;
+            }
+            let Ok(mut win) = self.win.try_borrow_mut() else {
+                return;
+            };
+            if self.repaint.replace(false) {
+                drop(win);
+                // a null window would invalidate every window on the desktop
+                let hwnd = self.hwnd.get();
+                if !hwnd.is_invalid() {
+                    unsafe {
+                        let _ = InvalidateRect(hwnd, None, false);
+                    }
+                }
+                continue;
+            }
+            let Some(event) = self.deferred.borrow_mut().pop_front() else {
+                return;
+            };
+
+            let handled = win.dispatch_event(&event);
+            drop(win);
+            if event.message == WM_CLOSE && handled == EventHandled::NotHandled {
+                mdi::destroy_window(event.hwnd);
+            }
+        }
+    }
+}
+
+/// Messages that are still delivered to a window after they've
+/// arrived while it was borrowed
+fn is_notification(message: u32) -> bool {
+    matches!(
+        message,
+        WM_SIZE
+            | WM_CLOSE
+            | WM_TIMER
+            | WM_COMMAND
+            | WM_DESTROY
+            | WM_NCDESTROY
+            | WM_DISPLAYCHANGE
+            | WM_DPICHANGED
+            | WM_USER_EVENT
+            | WM_EXECUTOR_WAKE
+            | WM_EXECUTOR_UPDATE
+            | WM_TRAY
+    )
+}
+
+/// A shared, reference counted handle to a window's state.
+///
+/// The state stays alive for as long as there's a handle to it or
+/// its native window exists, whichever is longer, so dropping every
+/// handle to an open window is fine.
+pub struct WinHandle<W: Win> {
+    cell: Rc<WinCell<W>>,
+}
+
+impl<W: Win> Clone for WinHandle<W> {
+    fn clone(&self) -> Self {
+        Self {
+            cell: self.cell.clone(),
+        }
+    }
+}
+
+impl<W: Win> WinHandle<W> {
+    pub fn new(mut win: W) -> Self {
+        let chrome = win.get_base().chrome.clone();
+        Self {
+            cell: Rc::new(WinCell {
+                win: RefCell::new(win),
+                hwnd: Cell::new(HWND::default()),
+                show_cmd: Cell::new(SW_NORMAL),
+                class: RefCell::new(None),
+                deferred: RefCell::new(VecDeque::new()),
+                repaint: Cell::new(false),
+                chrome,
+            }),
+        }
+    }
+
+    /// Creates the native window using the default
+    /// main window arguments
+    pub fn create_window(&self, title: &str) -> Result<HWND> {
+        self.create_window_with_args(title, &WinCreateArgs::default_win_main())
+    }
+
+    /// Creates the native window. The window mustn't be borrowed
+    /// while it's created, as `WM_CREATE` is handled straight away.
+    pub fn create_window_with_args(
+        &self,
+        title: &str,
+        create_args: &WinCreateArgs,
+    ) -> Result<HWND> {
+        create_args.validate()?;
+        let instance = match create_args.instance.is_invalid() {
+            true => WPModule::new().get_hinstance(),
+            false => create_args.instance,
+        };
+
+        let class = class::acquire::<W>(create_args, instance)?;
+        {
+            let mut win = self.borrow_mut();
+            let base = win.get_base();
+            base.background_color = create_args.background_color;
+            base.chrome.custom_frame.set(create_args.custom_frame);
+        }
+        let (x, y) = create_args
+            .position
+            .unwrap_or((CW_USEDEFAULT, CW_USEDEFAULT));
+
+        let hwnd = unsafe {
+            CreateWindowExW(
+                create_args.ex_style,
+                class.name(),
+                &HSTRING::from(title),
+                create_args.style,
+                x,
+                y,
+                create_args.window_width,
+                create_args.window_height,
+                create_args.parent.unwrap_or_default(),
+                None,
+                instance,
+                Some(Rc::as_ptr(&self.cell) as _),
+            )
+            .map_err(Error::WindowCreation)?
+        };
+        // released when the window is destroyed
+        *self.cell.class.borrow_mut() = Some(class);
+        self.cell.show_cmd.set(create_args.show_state.show_cmd());
+
+        for (size, icon) in [
+            (IconSize::Large, &create_args.large_icon),
+            (IconSize::Small, &create_args.small_icon),
+        ] {
+            if icon.is_some() {
+                self.borrow_mut().set_icon(size, icon.clone());
+            }
+        }
+
+        if let Some(placement) = &create_args.placement {
+            match placement::set(hwnd, placement) {
+                Ok(()) if placement.maximized => self.cell.show_cmd.set(SW_SHOWMAXIMIZED),
+                Ok(()) => {}
+                Err(err) => error::report(err),
+            }
+        }
+        Ok(hwnd)
+    }
+
+    /// The native window, or a null `HWND` if it hasn't been
+    /// created yet or has been destroyed
+    pub fn hwnd(&self) -> HWND {
+        self.cell.hwnd.get()
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.hwnd().is_invalid()
+    }
+
+    /// Shows the window as its `WinCreateArgs::show_state` asked,
+    /// without borrowing its state so the messages it sends are
+    /// handled straight away. A window restored to a maximized
+    /// placement is shown maximized.
+    pub fn show(&self) -> bool {
+        unsafe { ShowWindow(self.hwnd(), self.cell.show_cmd.get()).as_bool() }
+    }
+
+    /// Paints the window without borrowing its state
+    pub fn update(&self) -> bool {
+        unsafe { UpdateWindow(self.hwnd()).as_bool() }
+    }
+
+    /// Borrows the window's state.
+    ///
+    /// Panics if it's mutably borrowed, e.g. from inside
+    /// one of the window's own handlers.
+    pub fn borrow(&self) -> WinRef<'_, W> {
+        WinRef {
+            cell: &self.cell,
+            win: Some(self.cell.win.borrow()),
+        }
+    }
+
+    /// Mutably borrows the window's state.
+    ///
+    /// Panics if it's already borrowed, e.g. from inside
+    /// one of the window's own handlers.
+    pub fn borrow_mut(&self) -> WinRefMut<'_, W> {
+        WinRefMut {
+            cell: &self.cell,
+            win: Some(self.cell.win.borrow_mut()),
+        }
+    }
+
+    /// Mutably borrows the window's state, or returns
+    /// `None` if it's already borrowed
+    pub fn try_borrow_mut(&self) -> Option<WinRefMut<'_, W>> {
+        let win = self.cell.win.try_borrow_mut().ok()?;
+        Some(WinRefMut {
+            cell: &self.cell,
+            win: Some(win),
+        })
+    }
+
+    /// Calls `f` with the window's state mutably borrowed
+    pub fn with<R>(&self, f: impl FnOnce(&mut W) -> R) -> R {
+        f(&mut self.borrow_mut())
+    }
+}
+
+/// A shared borrow of a window's state. Messages that arrived
+/// while it was held are replayed when it's dropped.
+pub struct WinRef<'a, W: Win> {
+    cell: &'a WinCell<W>,
+    win: Option<Ref<'a, W>>,
+}
+
+impl<W: Win> Deref for WinRef<'_, W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        self.win.as_deref().expect("only released on drop")
+    }
+}
+
+impl<W: Win> Drop for WinRef<'_, W> {
+    fn drop(&mut self) {
+        self.win = None;
+        self.cell.replay_deferred();
+    }
+}
+
+/// A mutable borrow of a window's state. Messages that arrived
+/// while it was held are replayed when it's dropped.
+pub struct WinRefMut<'a, W: Win> {
+    cell: &'a WinCell<W>,
+    win: Option<RefMut<'a, W>>,
+}
+
+impl<W: Win> Deref for WinRefMut<'_, W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        self.win.as_deref().expect("only released on drop")
+    }
+}
+
+impl<W: Win> DerefMut for WinRefMut<'_, W> {
+    fn deref_mut(&mut self) -> &mut W {
+        self.win.as_deref_mut().expect("only released on drop")
+    }
+}
+
+impl<W: Win> Drop for WinRefMut<'_, W> {
+    fn drop(&mut self) {
+        self.win = None;
+        self.cell.replay_deferred();
+    }
+}
+
+/// The body of [`Win::wndproc`]
+pub(crate) fn wndproc<W: Win>(hwnd: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    let ptr = match message {
+        WM_NCCREATE => unsafe {
+            let createstruct = &*(lparam.0 as *const CREATESTRUCTW);
+            let ptr = match createstruct.dwExStyle.contains(WS_EX_MDICHILD) {
+                // an MDI child's create param is wrapped by the MDI client
+                true => {
+                    (*(createstruct.lpCreateParams as *const MDICREATESTRUCTW))
+                        .lParam
+                        .0 as *const WinCell<W>
+                }
+                false => createstruct.lpCreateParams as *const WinCell<W>,
+            };
+            if !ptr.is_null() {
+                // the native window keeps the state alive until WM_NCDESTROY
+                Rc::increment_strong_count(ptr);
+                SetWindowLongPtrW(hwnd, GWLP_USERDATA, ptr as isize);
+            }
+            ptr
+        },
+        _ => unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *const WinCell<W> },
+    };
+
+    if ptr.is_null() {
+        log::trace!("no window for message 0x{:04X} yet", message);
+        return mdi::default_proc(hwnd, message, wparam, lparam);
+    }
+
+    // held for the whole call, as WM_NCDESTROY releases the window's reference
+    let cell = unsafe {
+        Rc::increment_strong_count(ptr);
+        Rc::from_raw(ptr)
+    };
+    let event = Event {
+        hwnd,
+        message,
+        wparam,
+        lparam,
+    };
+
+    let result = match cell.win.try_borrow_mut() {
+        Ok(mut win) => {
+            if message == WM_NCCREATE {
+                cell.hwnd.set(hwnd);
+                win.set_hwnd(hwnd);
+            }
+            let handled = win.dispatch_event(&event);
+            drop(win);
+            match handled {
+                EventHandled::Handled(lresult) => {
+                    mdi::handled(hwnd, message, wparam, lparam);
+                    lresult
+                }
+                EventHandled::NotHandled => mdi::default_proc(hwnd, message, wparam, lparam),
+            }
+        }
+        Err(_) if message == WM_NCCREATE => {
+            log::error!("window was borrowed while it was being created");
+            LRESULT(0)
+        }
+        Err(_) => cell.defer(event),
+    };
+
+    if message == WM_NCDESTROY {
+        cell.hwnd.set(HWND::default());
+        cell.class.borrow_mut().take();
+        unsafe {
+            SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0);
+            Rc::decrement_strong_count(ptr);
+        }
+    }
+    cell.replay_deferred();
+    result
+}
@@ -0,0 +1,123 @@
+//! Icons from image data, for apps without a resource script.
+//!
+//! An [`Icon`] can be given to a window at creation with
+//! [`WinCreateArgs::with_large_icon`](super::win_create_args::WinCreateArgs::with_large_icon)
+//! and [`with_small_icon`](super::win_create_args::WinCreateArgs::with_small_icon),
+//! or later with [`Win::set_icon`](super::Win::set_icon). Windows
+//! scales it to fit, but looks best given a 32×32 large icon for
+//! Alt+Tab and a 16×16 small icon for the title bar and taskbar,
+//! at 100% scaling.
+
+use std::rc::Rc;
+use windows::Win32::{Foundation::*, UI::WindowsAndMessaging::*};
+
+use super::{
+    bitmap,
+    error::{Error, Result},
+};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Which of a window's two icons to set
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IconSize {
+    /// Shown in the title bar and the taskbar
+    Small,
+    /// Shown in Alt+Tab
+    Large,
+}
+
+impl IconSize {
+    pub(crate) fn wparam(self) -> WPARAM {
+        WPARAM(match self {
+            IconSize::Small => ICON_SMALL,
+            IconSize::Large => ICON_BIG,
+        } as usize)
+    }
+}
+
+#[derive(Debug)]
+struct IconHandle {
+    handle: HICON,
+}
+
+impl Drop for IconHandle {
+    fn drop(&mut self) {
+        let _ = unsafe { DestroyIcon(self.handle) };
+    }
+}
+
+/// An icon that can be shared between windows, and is
+/// destroyed once the last clone is dropped
+#[derive(Clone, Debug)]
+pub struct Icon(Rc<IconHandle>);
+
+impl Icon {
+    /// An icon from `width` × `height` RGBA pixels
+    pub fn from_rgba(rgba: &[u8], width: u32, height: u32) -> Result<Self> {
+        let icon = bitmap::create_icon(rgba, width, height, None)?;
+        Ok(Icon(Rc::new(IconHandle { handle: icon })))
+    }
+
+    /// An icon from the contents of a PNG or ICO file. An ICO
+    /// file's image closest to `size` pixels square is used.
+    pub fn from_bytes(bytes: &[u8], size: u32) -> Result<Self> {
+        let image = match bytes.starts_with(PNG_SIGNATURE) {
+            true => bytes,
+            false => ico_image(bytes, size)?,
+        };
+        let icon = unsafe {
+            CreateIconFromResourceEx(
+                image,
+                TRUE,
+                0x0003_0000,
+                size as i32,
+                size as i32,
+                LR_DEFAULTCOLOR,
+            )
+        }
+        .map_err(|_| Error::InvalidImage("the image couldn't be decoded"))?;
+        Ok(Icon(Rc::new(IconHandle { handle: icon })))
+    }
+
+    pub fn handle(&self) -> HICON {
+        self.0.handle
+    }
+}
+
+/// The image in an ICO file that's closest to `size`, preferring
+/// bigger ones and then more colours, which may be a PNG or a DIB
+pub(crate) fn ico_image(bytes: &[u8], size: u32) -> Result<&[u8]> {
+    let u16_at = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+    let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+
+    // ICONDIR: reserved, type (1 for icons), count
+    if bytes.len() < 6 || u16_at(0) != 0 || u16_at(2) != 1 {
+        return Err(Error::InvalidImage("not a PNG or ICO file"));
+    }
+    let count = u16_at(4) as usize;
+    if count == 0 || bytes.len() < 6 + count * 16 {
+        return Err(Error::InvalidImage("the ICO file is truncated"));
+    }
+
+    let mut best: Option<((bool, u32, u16), usize, usize)> = None;
+    for entry in (0..count).map(|i| 6 + i * 16) {
+        // a width of 0 means 256
+        let width = match bytes[entry] {
+            0 => 256,
+            width => width as u32,
+        };
+        let bit_count = u16_at(entry + 6);
+        let len = u32_at(entry + 8) as usize;
+        let offset = u32_at(entry + 12) as usize;
+        if offset.checked_add(len).is_none_or(|end| end > bytes.len()) {
+            return Err(Error::InvalidImage("the ICO file is truncated"));
+        }
+        let rank = (width < size, width.abs_diff(size), u16::MAX - bit_count);
+        if best.is_none_or(|(best, ..)| rank < best) {
+            best = Some((rank, offset, len));
+        }
+    }
+    let (_, offset, len) = best.expect("there's at least one entry");
+    Ok(&bytes[offset..offset + len])
+}
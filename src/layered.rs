@@ -0,0 +1,100 @@
+//! Layered windows, for overlays, splash screens and HUDs.
+//!
+//! A window can either be made translucent as a whole with
+//! [`Win::set_opacity`](super::Win::set_opacity) or have its content
+//! replaced by an RGBA image with per-pixel alpha with
+//! [`Win::update_layered`](super::Win::update_layered), in which case
+//! `on_paint` is no longer used. Fully transparent pixels of a
+//! per-pixel window don't take mouse input.
+
+use windows::Win32::{Foundation::*, Graphics::Gdi::*, UI::WindowsAndMessaging::*};
+
+use super::{bitmap, error::Result};
+
+fn ex_style(hwnd: HWND) -> WINDOW_EX_STYLE {
+    WINDOW_EX_STYLE(unsafe { GetWindowLongPtrW(hwnd, GWL_EXSTYLE) } as u32)
+}
+
+fn set_ex_style(hwnd: HWND, flags: WINDOW_EX_STYLE, set: bool) {
+    unsafe {
+        let ex_style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+        let ex_style = match set {
+            true => ex_style | flags.0 as isize,
+            false => ex_style & !(flags.0 as isize),
+        };
+        SetWindowLongPtrW(hwnd, GWL_EXSTYLE, ex_style);
+    }
+}
+
+fn alpha(opacity: f32) -> u8 {
+    (opacity.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+pub(crate) fn set_opacity(hwnd: HWND, opacity: f32) -> Result<()> {
+    set_ex_style(hwnd, WS_EX_LAYERED, true);
+    unsafe {
+        SetLayeredWindowAttributes(hwnd, COLORREF(0), alpha(opacity), LWA_ALPHA)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn update(hwnd: HWND, rgba: &[u8], width: u32, height: u32, opacity: f32) -> Result<()> {
+    bitmap::check_rgba(rgba, width, height)?;
+
+    unsafe {
+        // a window that's used SetLayeredWindowAttributes has to
+        // stop being layered before UpdateLayeredWindow will work
+        if GetLayeredWindowAttributes(hwnd, None, None, None).is_ok() {
+            set_ex_style(hwnd, WS_EX_LAYERED, false);
+        }
+    }
+    set_ex_style(hwnd, WS_EX_LAYERED, true);
+
+    let bgra = bitmap::rgba_to_bgra(rgba, true);
+    let bitmap = bitmap::create_dib(width, height, &bgra)?;
+    let size = SIZE {
+        cx: width as i32,
+        cy: height as i32,
+    };
+    let source = POINT::default();
+    let blend = BLENDFUNCTION {
+        BlendOp: AC_SRC_OVER as u8,
+        BlendFlags: 0,
+        SourceConstantAlpha: alpha(opacity),
+        AlphaFormat: AC_SRC_ALPHA as u8,
+    };
+
+    unsafe {
+        let screen = GetDC(None);
+        let mem_dc = CreateCompatibleDC(screen);
+        let old_bitmap = SelectObject(mem_dc, bitmap);
+        let result = UpdateLayeredWindow(
+            hwnd,
+            screen,
+            None,
+            Some(&size),
+            mem_dc,
+            Some(&source),
+            COLORREF(0),
+            Some(&blend),
+            ULW_ALPHA,
+        );
+        SelectObject(mem_dc, old_bitmap);
+        let _ = DeleteObject(bitmap);
+        let _ = DeleteDC(mem_dc);
+        ReleaseDC(None, screen);
+        result?;
+    }
+    Ok(())
+}
+
+/// Lets every click pass through the window to whatever
+/// is underneath, including other apps' windows
+pub(crate) fn set_click_through(hwnd: HWND, click_through: bool) -> Result<()> {
+    // only layered windows pass clicks on to other apps
+    if click_through && !ex_style(hwnd).contains(WS_EX_LAYERED) {
+        set_opacity(hwnd, 1.0)?;
+    }
+    set_ex_style(hwnd, WS_EX_TRANSPARENT, click_through);
+    Ok(())
+}
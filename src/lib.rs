@@ -10,9 +10,20 @@
 //! doing so, but currently it's just a blank window
 //! that can be drawn on with an onPaint event handler.
 
+pub use self::error::{Error, Result};
 pub use self::win::Win;
 
+use chrome::Chrome;
+use cursor::Cursor;
 use dc::DeviceContext;
+use frame::{FrameClock, FrameStats, FrameTiming};
+use handle::WinHandle;
+use icon::Icon;
+use proxy::UserEvents;
+use scroll::Scroll;
+use std::{any::Any, collections::HashMap, rc::Rc, time::Instant};
+use timer::Timers;
+use top_level::QuitPolicy;
 use win::MainWindow;
 use win_create_args::WinCreateArgs;
 use windows::{
@@ -23,16 +34,56 @@ use windows::{
     },
 };
 
+mod bitmap;
+pub mod child;
+pub mod chrome;
+pub mod class;
+pub mod cursor;
 pub mod dc;
+pub mod dialog;
+pub mod dpi;
+pub mod error;
+pub mod executor;
+pub mod frame;
+pub mod handle;
+pub mod icon;
 pub mod kbd;
+pub mod layered;
+pub mod mdi;
+pub mod menu;
+pub mod monitor;
 pub mod mouse;
+pub mod placement;
+pub mod proxy;
+pub mod scroll;
+pub mod timer;
+pub mod top_level;
+pub mod trace;
+pub mod tray;
 pub mod win;
 pub mod win_create_args;
 
+/// Posted to a window when a proxy has sent it a user event
+pub(crate) const WM_USER_EVENT: u32 = WM_APP;
+/// Posted to a window when futures on the executor have been woken
+pub(crate) const WM_EXECUTOR_WAKE: u32 = WM_APP + 1;
+/// Posted to a window when a spawned future has an update for it
+pub(crate) const WM_EXECUTOR_UPDATE: u32 = WM_APP + 2;
+/// Sent by the shell when something happens to one of a window's tray icons
+pub(crate) const WM_TRAY: u32 = WM_APP + 3;
+
 pub enum SendMessageParams {
     Close,
 }
 
+impl SendMessageParams {
+    fn params(&self) -> (u32, WPARAM, LPARAM) {
+        match self {
+            SendMessageParams::Close => (WM_CLOSE, WPARAM(0), LPARAM(0)),
+        }
+    }
+}
+
 /// Determines if an event handler handled the event
 #[derive(PartialEq)]
 pub enum EventHandled {
@@ -40,6 +91,14 @@ pub enum EventHandled {
     NotHandled,
 }
 
+/// Returned from `on_close_requested` to let
+/// a close go ahead or veto it
+#[derive(PartialEq, Debug)]
+pub enum CloseRequest {
+    Close,
+    Cancel,
+}
+
 /// An Event struct is what is
 /// passed as an abstraction
 /// instead of the wndproc args
@@ -51,6 +110,24 @@ pub struct Event {
     pub lparam: LPARAM,
 }
 
+/// A closure stored against a window which is handed the
+/// window back (as `dyn Any`) when it is run
+pub(crate) type WinCallback = Box<dyn FnMut(&mut dyn Any)>;
+
+pub(crate) fn win_callback<W, F>(mut callback: F) -> WinCallback
+where
+    W: Win,
+    F: FnMut(&mut W) + 'static,
+{
+    Box::new(move |win: &mut dyn Any| match win.downcast_mut::<W>() {
+        Some(win) => callback(win),
+        None => log::warn!(
+            "callback for a {} ignored, as it was run on another type of window",
+            std::any::type_name::<W>()
+        ),
+    })
+}
+
 #[derive(PartialEq, Debug)]
 pub enum SourceType {
     Menu,
@@ -73,6 +150,7 @@ $( $method:ident $methodargs:tt)*
 $({ $($contents:tt)* })? ) => {{
         #[allow(unused_imports)]
         use $crate::{WPApp, win_create_args::WinCreateArgs};
+        #[allow(unused_imports)]
         use windows::{core::*, Win32::Foundation::HINSTANCE};
         let create_args = WinCreateArgs {
             instance: HINSTANCE::default(),
@@ -81,10 +159,10 @@ $({ $($contents:tt)* })? ) => {{
             ..WinCreateArgs::default_win_main()
         };
         let mut app = WPApp::<$el>::new_with_config(create_args);
-        let mut title = PCWSTR::null();
-        $( title = w!($title);)?
-        if (title == PCWSTR::null()) {
-            title = w!("Default");
+        let mut title = "";
+        $( title = $title;)?
+        if title.is_empty() {
+            title = "Default";
         }
         app
     }};
@@ -100,46 +178,42 @@ macro_rules! default_win_impl {
             self.base.hwnd = hwnd;
         }
 
-        fn to_self_ptr(ptr: *mut ::core::ffi::c_void) -> *mut Self {
-            ptr as *mut Self
-        }
-        //    ptr_self as _
-        fn raw_ptr_isize(raw_ptr: *mut Self) -> isize {
-            raw_ptr as _
-        }
-
-        fn get_canary(&self) -> i32 {
-            self.base.canary
-        }
-
         fn get_base(&mut self) -> &mut BaseWin {
             &mut self.base
         }
+        fn base(&self) -> &BaseWin {
+            &self.base
+        }
     };
 }
 
+#[derive(Default)]
 pub struct BaseWin {
     pub hwnd: HWND,
-    pub canary: i32,
     pub tm: TEXTMETRICW,
     pub x: i32,
     pub y: i32,
+    pub(crate) timers: Timers,
+    pub(crate) user_events: UserEvents,
+    pub(crate) windowed: Option<Windowed>,
+    pub(crate) background_color: Option<u32>,
+    pub(crate) chrome: Rc<Chrome>,
+    pub(crate) cursor: Option<Cursor>,
+    pub(crate) cursor_regions: Vec<(RECT, Cursor)>,
+    pub(crate) large_icon: Option<Icon>,
+    pub(crate) small_icon: Option<Icon>,
+    pub(crate) menu_actions: HashMap<u16, WinCallback>,
+    pub(crate) scroll: Option<Scroll>,
     // cx_char: i32,
     // cx_caps: i32,
     // cy_char: i32,
     // max_width: i32,
 }
 
-impl Default for BaseWin {
-    fn default() -> Self {
-        Self {
-            hwnd: HWND::default(),
-            canary: 99,
-            tm: TEXTMETRICW::default(),
-            x: 0,
-            y: 0,
-        }
-    }
+/// What a window looked like before it went fullscreen
+pub(crate) struct Windowed {
+    style: isize,
+    placement: WINDOWPLACEMENT,
 }
 
 impl BaseWin {
@@ -170,61 +244,11 @@ impl BaseWin {
     pub fn on_resize(&mut self, x: i32, y: i32) -> EventHandled {
         self.x = x;
         self.y = y;
-        // let si = SCROLLINFO {
-        //     cbSize: mem::size_of::<SCROLLINFO>() as u32,
-        //     fMask: SIF_RANGE | SIF_PAGE,
-        //     nMin: 0,
-        //     // nMax: sys_metrics.len() as i32 - 1,
-        //     nMax: self.text_out.len() as i32 - 1,
-        //     nPage: self.cy_client as u32 / self.cy_client as u32,
-        //     ..Default::default()
-        // };
-
-        // set_scroll_info(event.hwnd, SB_VERT, &si, TRUE);
-        // let si = SCROLLINFO {
-        //     cbSize: mem::size_of::<SCROLLINFO>() as u32,
-        //     fMask: SIF_RANGE | SIF_PAGE,
-        //     nMin: 0,
-        //     nMax: 2 + self.max_width / self.cx_char,
-        //     nPage: self.cx_client as u32 / self.cx_char as u32,
-        //     ..Default::default()
-        // };
-        // set_scroll_info(event.hwnd, SB_HORZ, &si, TRUE);
+        if let Some(scroll) = &mut self.scroll {
+            scroll::update_bars(self.hwnd, scroll, (x, y));
+        }
         EventHandled::Handled(LRESULT(0))
     }
-
-    // fn on_paint(&self, event: &window::Event) -> window::EventHandled {
-    //     let mut ps: PAINTSTRUCT = PAINTSTRUCT::default();
-    //     let hdc = begin_paint(event.hwnd, &mut ps);
-
-    //     let mut si = SCROLLINFO {
-    //         cbSize: mem::size_of::<SCROLLINFO>() as u32,
-    //         fMask: SIF_POS,
-    //         ..Default::default()
-    //     };
-    //     let _ = get_scroll_info(event.hwnd, SB_VERT, &mut si);
-    //     let i_vert_pos = si.nPos;
-    //     let _ = get_scroll_info(event.hwnd, SB_HORZ, &mut si);
-    //     let i_horz_pos = si.nPos;
-
-    //     let i_paint_begin = cmp::max(0, i_vert_pos + ps.rcPaint.top / self.cy_char);
-    //     let i_paint_end = cmp::min(
-    //         self.text_out.len() as i32 - 1,
-    //         i_vert_pos + ps.rcPaint.bottom / self.cy_char,
-    //     );
-
-    //     for (i, line) in self.text_out.iter().enumerate() {
-    //         if (i as i32) < i_paint_begin || (i as i32) > i_paint_end {
-    //             continue;
-    //         }
-    //         let x = self.cx_char * (1 - i_horz_pos);
-    //         let y = self.cy_char * (i as i32 - i_vert_pos);
-    //         text_out(hdc, x, y, line);
-    //     }
-
-    //     end_paint(event.hwnd, &ps);
-    //     window::EventHandled::Handled(LRESULT(0))
-    // }
 }
 
 // pub struct WPWin {
@@ -276,7 +300,7 @@ impl WPModule {
                     hinst: hinst.into(),
                 },
                 Err(error) => {
-                    println!("error getting hinstance: {:?}", error);
+                    log::error!("error getting hinstance: {:?}", error);
                     WPModule {
                         hinst: HINSTANCE::default(),
                     }
@@ -292,10 +316,12 @@ impl WPModule {
 
 pub struct WPApp<T: Win> {
     module: WPModule,
-    pub main_win: T,
+    pub main_win: WinHandle<T>,
     exit_code: WPARAM,
     accel: Option<HACCEL>,
     create_args: Option<WinCreateArgs>,
+    frame_timing: Option<FrameTiming>,
+    frame_stats: FrameStats,
 }
 
 pub fn new_app_with_main_window() -> WPApp<MainWindow> {
@@ -305,15 +331,16 @@ pub fn new_app_with_main_window() -> WPApp<MainWindow> {
 impl<T: Win> WPApp<T> {
     pub fn new() -> Self {
         let module = WPModule::new();
-        let main_win = T::new(module.hinst);
+        let main_win = WinHandle::new(T::new(module.hinst));
 
-        // self.main_win = Some(main_win);
         WPApp {
             module,
             main_win,
             exit_code: WPARAM(0),
             accel: None,
             create_args: None,
+            frame_timing: None,
+            frame_stats: FrameStats::default(),
         }
     }
 
@@ -323,7 +350,7 @@ impl<T: Win> WPApp<T> {
         app
     }
 
-    pub fn init(&mut self, title: PCWSTR) -> Result<()> {
+    pub fn init(&mut self, title: &str) -> Result<()> {
         match &self.create_args {
             None => {
                 self.main_win.create_window(title)?;
@@ -332,23 +359,61 @@ impl<T: Win> WPApp<T> {
                 self.main_win.create_window_with_args(title, create_args)?;
             }
         }
+        executor::set_wake_window(self.main_win.hwnd());
+        top_level::register_main(self.main_win.hwnd());
 
         match self.load_accelerators() {
             Ok(accel) => self.accel = Some(accel),
-            Err(_err) => println!("couldn't load accelerator"),
+            Err(err) => log::debug!("couldn't load accelerator: {:?}", err),
         }
 
         Ok(())
     }
 
     pub fn load_accelerators(&self) -> Result<HACCEL> {
-        unsafe { LoadAcceleratorsW(self.get_hinstance(), w!("AppAccel")) }
+        unsafe { LoadAcceleratorsW(self.get_hinstance(), w!("AppAccel")) }.map_err(|source| {
+            Error::ResourceLoad {
+                name: "AppAccel".to_string(),
+                source,
+            }
+        })
     }
 
     pub fn get_hinstance(&self) -> HINSTANCE {
         self.module.get_hinstance()
     }
 
+    /// Logs every message dispatched to the app's windows,
+    /// see [`trace`]
+    pub fn set_message_tracing(&mut self, enabled: bool) {
+        trace::set_message_tracing(enabled);
+    }
+
+    /// Sets the hook that errors raised inside message
+    /// handlers are reported to, see [`error::report`]
+    pub fn set_error_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(&Error) + 'static,
+    {
+        error::set_error_hook(hook);
+    }
+
+    /// Opts into per-monitor DPI awareness, see [`dpi`].
+    /// It has to be called before `init`.
+    pub fn enable_dpi_awareness(&mut self) -> Result<()> {
+        dpi::enable_per_monitor_awareness()
+    }
+
+    pub fn set_quit_policy(&mut self, policy: QuitPolicy) {
+        top_level::set_quit_policy(policy);
+    }
+
+    /// Opens another top-level window alongside the main
+    /// window, see [`top_level::open_window`]
+    pub fn open_window<W: Win>(&self, title: &str) -> Result<WinHandle<W>> {
+        top_level::open_window::<W>(title)
+    }
+
     pub fn exit_code(&self) -> WPARAM {
         self.exit_code
     }
@@ -363,7 +428,7 @@ impl<T: Win> WPApp<T> {
 
     pub fn translate_accelerator(&self, accel: HACCEL, msg: MSG) -> bool {
         let msg = &msg as *const _;
-        unsafe { TranslateAcceleratorW(self.main_win.get_hwnd(), accel, msg) > 0 }
+        unsafe { TranslateAcceleratorW(self.main_win.hwnd(), accel, msg) > 0 }
     }
 
     pub fn translate_message(msg: &MSG) -> bool {
@@ -376,50 +441,105 @@ impl<T: Win> WPApp<T> {
         }
     }
 
-    pub fn run(&mut self) -> () {
+    /// Runs `run` as a game loop, calling `Win::on_frame` paced by
+    /// `timing` instead of `Win::do_idle`
+    pub fn set_frame_timing(&mut self, timing: FrameTiming) {
+        self.frame_timing = Some(timing);
+    }
+
+    pub fn frame_stats(&self) -> &FrameStats {
+        &self.frame_stats
+    }
+
+    pub fn run(&mut self) {
         self.main_win.show();
         self.main_win.update();
 
         let mut msg = MSG::default();
-        let mut peek = true;
+        match self.frame_timing {
+            None => self.run_idle(&mut msg),
+            Some(timing) => self.run_frames(&mut msg, timing),
+        }
+        top_level::close_all();
+        class::sweep();
+
+        self.exit_code = msg.wParam
+    }
 
-        while peek || Self::get_message(&mut msg) {
-            if peek {
+    /// Calls `do_idle` while the queue is empty until it
+    /// returns false, then blocks in `GetMessage`
+    fn run_idle(&mut self, msg: &mut MSG) {
+        let mut idle = true;
+
+        loop {
+            if idle {
                 // Use PeekMessage instead of GetMessage
-                if Self::peek_message(&mut msg) {
-                    peek = self.main_win.do_idle();
+                if !Self::peek_message(msg) {
+                    idle = self.main_win.borrow_mut().do_idle();
                     continue;
                 }
+            } else if !Self::get_message(msg) {
+                break;
+            }
+            if msg.message == WM_QUIT {
+                break;
+            }
+            self.process_message(msg);
+        }
+    }
+
+    /// Drains the queue, then runs a frame if one is due or waits
+    /// for either the next frame or a message to arrive
+    fn run_frames(&mut self, msg: &mut MSG, timing: FrameTiming) {
+        let mut clock = FrameClock::new(timing, Instant::now());
+
+        loop {
+            while Self::peek_message(msg) {
                 if msg.message == WM_QUIT {
-                    break;
+                    return;
                 }
+                self.process_message(msg);
             }
-            let accel_message = match self.accel {
-                None => false,
-                Some(accel) => self.translate_accelerator(accel, msg),
-            };
-            if !accel_message {
-                Self::translate_message(&msg);
-                Self::dispatch_message(&msg);
+
+            let now = Instant::now();
+            match clock.time_until_next(now) {
+                Some(wait) => unsafe {
+                    // rounded up, as a zero timeout would spin until the frame is due
+                    let timeout = wait.as_micros().div_ceil(1000).min(u32::MAX as u128) as u32;
+                    MsgWaitForMultipleObjects(None, false, timeout, QS_ALLINPUT);
+                },
+                None => {
+                    for delta in clock.tick(now, &mut self.frame_stats) {
+                        self.main_win
+                            .borrow_mut()
+                            .on_frame(delta, &self.frame_stats);
+                    }
+                }
             }
         }
+    }
 
-        self.exit_code = msg.wParam
+    fn process_message(&self, msg: &MSG) {
+        if msg.message == WM_EXECUTOR_WAKE {
+            executor::run_ready();
+            return;
+        }
+        if mdi::translate_accelerator(msg) {
+            class::sweep();
+            return;
+        }
+        let accel_message = match self.accel {
+            None => false,
+            Some(accel) => self.translate_accelerator(accel, *msg),
+        };
+        if !accel_message {
+            Self::translate_message(msg);
+            Self::dispatch_message(msg);
+        }
+        class::sweep();
     }
 }
 
-// pub fn set_scroll_info(hwnd: HWND, sb_option: SCROLLBAR_CONSTANTS, si: &SCROLLINFO, redraw: BOOL) {
-//     unsafe {
-//         SetScrollInfo(hwnd, sb_option, si, redraw);
-//     }
-// }
-
-// pub fn get_scroll_info(hwnd: HWND, sb_option: SCROLLBAR_CONSTANTS, si: &mut SCROLLINFO) {
-//     unsafe {
-//         GetScrollInfo(hwnd, sb_option, si);
-//     }
-// }
-
 // pub fn text_out(hdc: HDC, x: i32, y: i32, text: &str) {
 //     unsafe {
 //         TextOutW(hdc, x, y, &mut get_utf16_vec(text)[..]);
@@ -427,16 +547,33 @@ impl<T: Win> WPApp<T> {
 // }
 
 pub fn load_icon(inst: HINSTANCE, name: PCWSTR) -> Result<HICON> {
-    match hword(name.0 as isize) {
+    let icon = match hword(name.0 as isize) {
         0 => unsafe { LoadIconW(None, name) },
         _ => unsafe { LoadIconW(inst, name) },
-    }
+    };
+    icon.map_err(|source| Error::ResourceLoad {
+        name: resource_name(name),
+        source,
+    })
 }
 
 pub fn load_cursor(inst: Option<HINSTANCE>, name: PCWSTR) -> Result<HCURSOR> {
-    match inst {
+    let cursor = match inst {
         None => unsafe { LoadCursorW(None, name) },
         Some(inst) => unsafe { LoadCursorW(inst, name) },
+    };
+    cursor.map_err(|source| Error::ResourceLoad {
+        name: resource_name(name),
+        source,
+    })
+}
+
+/// A resource name for error messages, which may
+/// be a string or an integer id
+fn resource_name(name: PCWSTR) -> String {
+    match hword(name.0 as isize) {
+        0 => format!("#{}", lword(name.0 as isize)),
+        _ => unsafe { name.to_string() }.unwrap_or_default(),
     }
 }
 
@@ -498,4 +635,382 @@ pub fn hword(val: isize) -> i32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn proxy_can_be_sent_to_other_threads() {
+        fn assert_send_clone<P: Send + Clone>() {}
+        assert_send_clone::<proxy::WinProxy<String>>();
+    }
+
+    #[test]
+    fn proxies_need_a_created_window() {
+        let win = WinHandle::new(MainWindow::new(HINSTANCE::default()));
+        let proxy = win.borrow_mut().create_proxy::<u32>();
+        assert!(matches!(proxy.send(1), Err(Error::WindowClosed)));
+        assert!(win.borrow_mut().get_base().user_events.next().is_none());
+    }
+
+    #[test]
+    fn timers_need_a_created_window() {
+        let win = WinHandle::new(MainWindow::new(HINSTANCE::default()));
+        let timer = win
+            .borrow_mut()
+            .set_timer(std::time::Duration::from_millis(10), |_| {});
+        assert!(matches!(timer, Err(Error::WindowClosed)));
+    }
+
+    #[test]
+    fn futures_need_a_created_window() {
+        let win = WinHandle::new(MainWindow::new(HINSTANCE::default()));
+        let spawned = win.borrow().spawn_local(async { |_: &mut MainWindow| {} });
+        assert!(matches!(spawned, Err(Error::WindowClosed)));
+    }
+
+    #[test]
+    fn window_state_is_shared_between_handles() {
+        let win = WinHandle::new(MainWindow::new(HINSTANCE::default()));
+        let other = win.clone();
+        assert!(!win.is_open());
+
+        win.borrow_mut().get_base().x = 5;
+        assert_eq!(other.borrow_mut().get_base().x, 5);
+
+        let borrowed = other.borrow();
+        assert!(win.try_borrow_mut().is_none());
+        drop(borrowed);
+        assert!(win.try_borrow_mut().is_some());
+    }
+
+    #[test]
+    fn closing_the_remaining_windows_forgets_them() {
+        top_level::set_quit_policy(QuitPolicy::LastWindowClosed);
+        top_level::register_main(HWND(1 as _));
+        assert_eq!(top_level::window_count(), 1);
+        top_level::close_all();
+        assert_eq!(top_level::window_count(), 0);
+    }
+
+    #[test]
+    fn each_window_type_gets_its_own_class() {
+        struct OtherWindow(BaseWin);
+        impl Win for OtherWindow {
+            fn new(_inst: HINSTANCE) -> Self {
+                OtherWindow(BaseWin::default())
+            }
+            fn get_hwnd(&self) -> HWND {
+                self.0.hwnd
+            }
+            fn set_hwnd(&mut self, hwnd: HWND) {
+                self.0.hwnd = hwnd;
+            }
+            fn get_base(&mut self) -> &mut BaseWin {
+                &mut self.0
+            }
+            fn base(&self) -> &BaseWin {
+                &self.0
+            }
+            fn set_child(&mut self, _child: win::Component) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        let main = class::generated_name::<MainWindow>();
+        assert!(main.ends_with(".MainWindow"));
+        assert_eq!(main, class::generated_name::<MainWindow>());
+        assert_ne!(main, class::generated_name::<OtherWindow>());
+    }
+
+    #[test]
+    fn mdi_children_need_an_mdi_frame() {
+        let frame = HWND(1 as _);
+        let child =
+            mdi::open_child::<MainWindow>(frame, "Doc", &WinCreateArgs::default_mdi_child());
+        assert!(matches!(child, Err(Error::InvalidCreateArgs(_))));
+
+        let client = HWND(2 as _);
+        mdi::add_client(frame, client);
+        assert_eq!(mdi::client(frame), Some(client));
+        mdi::frame_destroyed(frame);
+        assert_eq!(mdi::client(frame), None);
+    }
+
+    #[test]
+    fn handled_frame_commands_stay_out_of_mdi_children() {
+        let passed = |id: u32| mdi::frame_must_pass(WM_COMMAND, WPARAM(id as usize));
+        assert!(!passed(100));
+        assert!(!passed(menu::FIRST_ACTION_ID as u32));
+        assert!(!passed(SC_CLOSE));
+        assert!(passed(mdi::FIRST_CHILD_ID + 2));
+        assert!(!mdi::frame_must_pass(WM_SIZE, WPARAM(0)));
+        assert!(mdi::frame_must_pass(WM_SETFOCUS, WPARAM(0)));
+    }
+
+    #[test]
+    fn menu_actions_need_unique_ids() {
+        use menu::{Menu, MenuItem, FIRST_ACTION_ID};
+
+        let mut actions = HashMap::new();
+        let menu = Menu::new()
+            .with_item(MenuItem::action("&Save", |_: &mut MainWindow| {}))
+            .with_item(MenuItem::command("&Open", FIRST_ACTION_ID));
+        let built = menu.build(true, &mut actions).unwrap();
+        assert!(!actions.contains_key(&FIRST_ACTION_ID));
+        assert_eq!(actions.len(), 1);
+        unsafe {
+            let _ = DestroyMenu(built);
+        }
+
+        let menu = Menu::new()
+            .with_item(MenuItem::command("&Open", 100))
+            .with_submenu(
+                "&Recent",
+                Menu::new()
+                    .with_item(MenuItem::action("&Open", |_: &mut MainWindow| {}).with_id(100)),
+            );
+        assert!(matches!(
+            menu.build(true, &mut HashMap::new()),
+            Err(Error::DuplicateMenuId(100))
+        ));
+    }
+
+    #[test]
+    fn placements_are_clamped_onto_the_monitor() {
+        let work_area = RECT {
+            left: 0,
+            top: 0,
+            right: 1920,
+            bottom: 1040,
+        };
+        let off_right = RECT {
+            left: 2500,
+            top: 100,
+            right: 3300,
+            bottom: 700,
+        };
+        let clamped = placement::clamp_to_area(off_right, &work_area);
+        assert_eq!(
+            (clamped.left, clamped.top, clamped.right, clamped.bottom),
+            (1120, 100, 1920, 700)
+        );
+
+        let too_big = RECT {
+            left: -50,
+            top: -50,
+            right: 3000,
+            bottom: 2000,
+        };
+        let clamped = placement::clamp_to_area(too_big, &work_area);
+        assert_eq!(
+            (clamped.left, clamped.top, clamped.right, clamped.bottom),
+            (0, 0, 1920, 1040)
+        );
+    }
+
+    #[test]
+    fn create_args_reject_conflicting_styles() {
+        assert!(WinCreateArgs::default_win_main().validate().is_ok());
+        assert!(WinCreateArgs::default_dialog().validate().is_ok());
+
+        let child_popup = WinCreateArgs::default_dialog().with_style(WS_CHILD | WS_POPUP);
+        assert!(matches!(
+            child_popup.validate(),
+            Err(Error::InvalidCreateArgs(_))
+        ));
+
+        let orphan = WinCreateArgs::default()
+            .with_style(WS_CHILD)
+            .with_size(10, 10);
+        assert!(orphan.validate().is_err());
+        assert!(orphan.with_parent(HWND(1 as _)).validate().is_ok());
+    }
+
+    #[test]
+    fn rgba_pixels_are_premultiplied_for_layered_windows() {
+        let rgba = [255, 128, 0, 128, 10, 20, 30, 255];
+        assert_eq!(
+            bitmap::rgba_to_bgra(&rgba, true),
+            vec![0, 64, 128, 128, 30, 20, 10, 255]
+        );
+        assert_eq!(
+            bitmap::rgba_to_bgra(&rgba, false),
+            vec![0, 128, 255, 128, 30, 20, 10, 255]
+        );
+        assert!(bitmap::check_rgba(&rgba, 2, 1).is_ok());
+        assert!(bitmap::check_rgba(&rgba, 2, 2).is_err());
+    }
+
+    #[test]
+    fn custom_frame_edges_resize_the_window() {
+        use chrome::{edge_hit_test, HitTest};
+
+        let rect = RECT {
+            left: 0,
+            top: 0,
+            right: 800,
+            bottom: 600,
+        };
+        let hit = |x, y| edge_hit_test(POINT { x, y }, &rect, 8);
+        assert_eq!(hit(400, 300), HitTest::Client);
+        assert_eq!(hit(2, 300), HitTest::Left);
+        assert_eq!(hit(795, 300), HitTest::Right);
+        assert_eq!(hit(400, 599), HitTest::Bottom);
+        assert_eq!(hit(3, 3), HitTest::TopLeft);
+        assert_eq!(hit(799, 599), HitTest::BottomRight);
+    }
+
+    #[test]
+    fn frame_queries_are_answered_while_the_window_is_borrowed() {
+        let win = WinHandle::new(MainWindow::new(HINSTANCE::default()));
+        let mut borrowed = win.borrow_mut();
+        let chrome = borrowed.get_base().chrome.clone();
+        let mut params = NCCALCSIZE_PARAMS::default();
+        let calc_size = Event {
+            hwnd: borrowed.get_hwnd(),
+            message: WM_NCCALCSIZE,
+            wparam: WPARAM(1),
+            lparam: LPARAM(&mut params as *mut _ as isize),
+        };
+
+        // toggled from a handler, as the frame change is sent
+        let _ = borrowed.set_custom_frame(true);
+        assert!(chrome::answer_borrowed(&chrome, &calc_size) != EventHandled::NotHandled);
+        let _ = borrowed.set_custom_frame(false);
+        assert!(chrome::answer_borrowed(&chrome, &calc_size) == EventHandled::NotHandled);
+
+        let mut info = MINMAXINFO::default();
+        let min_max_info = Event {
+            message: WM_GETMINMAXINFO,
+            lparam: LPARAM(&mut info as *mut _ as isize),
+            ..calc_size
+        };
+        borrowed.set_min_size(Some((320, 240)));
+        chrome::answer_borrowed(&chrome, &min_max_info);
+        assert_eq!((info.ptMinTrackSize.x, info.ptMinTrackSize.y), (320, 240));
+    }
+
+    #[test]
+    fn logical_units_scale_with_dpi() {
+        assert_eq!(dpi::to_physical(100, 144), 150);
+        assert_eq!(dpi::to_logical(150, 144), 100);
+        assert_eq!(dpi::to_logical(-3, 192), -2);
+        assert_eq!(dpi::to_logical(7, dpi::DEFAULT_DPI), 7);
+        assert_eq!(dpi::scale_factor(120), 1.25);
+    }
+
+    #[test]
+    fn ico_files_use_the_closest_image_that_is_big_enough() {
+        let entry = |width: u8, offset: u32| {
+            let mut entry = vec![width, width, 0, 0, 1, 0, 32, 0];
+            entry.extend_from_slice(&4u32.to_le_bytes());
+            entry.extend_from_slice(&offset.to_le_bytes());
+            entry
+        };
+        let mut ico = vec![0, 0, 1, 0, 2, 0];
+        ico.extend(entry(16, 38));
+        ico.extend(entry(32, 42));
+        ico.extend_from_slice(b"smalLARG");
+
+        assert_eq!(icon::ico_image(&ico, 16).unwrap(), b"smal");
+        assert_eq!(icon::ico_image(&ico, 24).unwrap(), b"LARG");
+        assert_eq!(icon::ico_image(&ico, 48).unwrap(), b"LARG");
+        assert!(matches!(
+            icon::ico_image(&ico[..30], 16),
+            Err(Error::InvalidImage(_))
+        ));
+        assert!(icon::ico_image(b"GIF89a", 16).is_err());
+    }
+
+    #[test]
+    fn scroll_bar_commands_stay_within_the_content() {
+        use windows::Win32::UI::WindowsAndMessaging::*;
+
+        let position = |command, offset| scroll::scroll_position(command, offset, 70, 10, 50, 200);
+        assert_eq!(position(SB_LINEDOWN, 0), 10);
+        assert_eq!(position(SB_LINEUP, 5), 0);
+        assert_eq!(position(SB_PAGEDOWN, 180), 200);
+        assert_eq!(position(SB_THUMBTRACK, 0), 70);
+        assert_eq!(position(SB_BOTTOM, 0), 200);
+        assert_eq!(position(SB_ENDSCROLL, 30), 30);
+    }
+
+    #[test]
+    fn fixed_step_frames_catch_up_with_real_time() {
+        use std::time::Duration;
+
+        let step = Duration::from_millis(10);
+        let start = Instant::now();
+        let mut clock = FrameClock::new(FrameTiming::FixedStep(step), start);
+        let mut stats = FrameStats::default();
+
+        assert_eq!(clock.time_until_next(start), None);
+        assert!(clock.tick(start, &mut stats).is_empty());
+        assert_eq!(clock.time_until_next(start), Some(step));
+
+        let later = start + Duration::from_millis(35);
+        assert_eq!(clock.tick(later, &mut stats), vec![step; 3]);
+        assert_eq!(stats.frame_count(), 3);
+        assert_eq!(stats.max_frame_time(), Duration::from_millis(35) / 3);
+    }
+
+    #[test]
+    fn target_fps_passes_real_elapsed_time() {
+        use std::time::Duration;
+
+        let start = Instant::now();
+        let mut clock = FrameClock::new(FrameTiming::TargetFps(50), start);
+        let mut stats = FrameStats::default();
+        clock.tick(start, &mut stats);
+        assert_eq!(stats.frame_count(), 0);
+
+        let later = start + Duration::from_millis(25);
+        assert_eq!(
+            clock.tick(later, &mut stats),
+            vec![Duration::from_millis(25)]
+        );
+        assert_eq!(stats.frame_count(), 1);
+        assert_eq!(
+            clock.time_until_next(later),
+            Some(Duration::from_millis(15))
+        );
+    }
+
+    #[test]
+    fn traced_messages_have_readable_names() {
+        let event = Event {
+            hwnd: HWND::default(),
+            message: WM_SIZE,
+            wparam: WPARAM(0),
+            lparam: LPARAM((600 << 16) | 800),
+        };
+        assert_eq!(trace::describe(&event), "WM_SIZE 800x600");
+        assert_eq!(trace::message_name(WM_PAINT), "WM_PAINT");
+        assert_eq!(trace::message_name(WM_APP + 3), "WM_APP+3");
+    }
+
+    #[test]
+    fn tray_callbacks_carry_the_icon_and_position() {
+        use tray::{TrayEvent, TrayEventKind};
+        use windows::Win32::UI::Shell::NIN_SELECT;
+
+        let event = |notification: u32, id: isize| Event {
+            hwnd: HWND::default(),
+            message: WM_TRAY,
+            wparam: WPARAM(((-5i16 as u16 as usize) << 16) | 120),
+            lparam: LPARAM((id << 16) | notification as isize),
+        };
+        assert_eq!(
+            tray::event(&event(NIN_SELECT, 2)),
+            Some(TrayEvent {
+                id: 2,
+                kind: TrayEventKind::Click,
+                x: 120,
+                y: -5,
+            })
+        );
+        assert_eq!(
+            tray::event(&event(WM_CONTEXTMENU, 7)).map(|e| e.kind),
+            Some(TrayEventKind::RightClick)
+        );
+        assert_eq!(tray::event(&event(WM_MOUSEMOVE, 2)), None);
+    }
 }
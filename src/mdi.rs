@@ -0,0 +1,237 @@
+//! MDI, documents in child windows inside a frame window.
+//!
+//! A window becomes an MDI frame with
+//! [`Win::enable_mdi`](super::Win::enable_mdi), which fills its
+//! client area with an MDI client window. Document windows of any
+//! [`Win`] type are opened inside it with
+//! [`Win::open_mdi_child`](super::Win::open_mdi_child), and can be
+//! cascaded or tiled. They can be maximized to fill the frame,
+//! minimized to icons along its bottom, and are listed in the
+//! frame's window menu if it has one. Ctrl+F4 closes the active
+//! child and Ctrl+F6 moves to the next.
+
+use std::{cell::RefCell, ffi::c_void};
+use windows::{
+    core::*,
+    Win32::{Foundation::*, UI::WindowsAndMessaging::*},
+};
+
+use super::{error::Result, handle::WinHandle, win::Win, win_create_args::WinCreateArgs, WPModule};
+
+/// The command id of the first child listed in the window
+/// menu, with the rest following on from it, above the ids
+/// given to menu actions
+pub const FIRST_CHILD_ID: u32 = 0xFF00;
+
+thread_local! {
+    // (frame, client) for every MDI frame
+    static CLIENTS: RefCell<Vec<(isize, isize)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Which default window procedure a window needs
+enum Role {
+    Window,
+    Frame(HWND),
+    Child,
+}
+
+fn role(hwnd: HWND) -> Role {
+    let client = CLIENTS.with(|clients| {
+        let clients = clients.borrow();
+        match clients.is_empty() {
+            true => None,
+            false => Some(
+                clients
+                    .iter()
+                    .find(|(frame, _)| *frame == hwnd.0 as isize)
+                    .map(|(_, client)| *client),
+            ),
+        }
+    });
+    match client {
+        // no MDI frames, so no need to look any further
+        None => Role::Window,
+        Some(Some(client)) => Role::Frame(HWND(client as *mut c_void)),
+        Some(None) if is_child(hwnd) => Role::Child,
+        Some(None) => Role::Window,
+    }
+}
+
+fn is_child(hwnd: HWND) -> bool {
+    let ex_style = unsafe { GetWindowLongPtrW(hwnd, GWL_EXSTYLE) };
+    ex_style as u32 & WS_EX_MDICHILD.0 != 0
+}
+
+/// The default handling for a message a window has left `NotHandled`
+pub(crate) fn default_proc(hwnd: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    unsafe {
+        match role(hwnd) {
+            Role::Window => DefWindowProcW(hwnd, message, wparam, lparam),
+            Role::Frame(client) => DefFrameProcW(hwnd, client, message, wparam, lparam),
+            Role::Child => DefMDIChildProcW(hwnd, message, wparam, lparam),
+        }
+    }
+}
+
+/// Passes on the messages an MDI frame or child has to pass to
+/// `DefFrameProc` or `DefMDIChildProc` even if it's handled them,
+/// e.g. so the child chosen from the window menu is activated
+pub(crate) fn handled(hwnd: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) {
+    unsafe {
+        match role(hwnd) {
+            Role::Window => {}
+            Role::Frame(client) => {
+                if frame_must_pass(message, wparam) {
+                    DefFrameProcW(hwnd, client, message, wparam, lparam);
+                }
+            }
+            Role::Child => {
+                let must_pass = matches!(
+                    message,
+                    WM_CHILDACTIVATE
+                        | WM_GETMINMAXINFO
+                        | WM_MENUCHAR
+                        | WM_MOVE
+                        | WM_NEXTMENU
+                        | WM_SETFOCUS
+                        | WM_SIZE
+                        | WM_SYSCOMMAND
+                );
+                if must_pass {
+                    DefMDIChildProcW(hwnd, message, wparam, lparam);
+                }
+            }
+        }
+    }
+}
+
+/// Whether a frame passes a message it's handled on to
+/// `DefFrameProc`. Other commands would be passed on to the
+/// active child and run twice, and a handled `WM_SIZE` has
+/// laid out the client window itself.
+pub(crate) fn frame_must_pass(message: u32, wparam: WPARAM) -> bool {
+    match message {
+        // choosing a child from the window menu
+        WM_COMMAND => wparam.0 as u16 as u32 >= FIRST_CHILD_ID,
+        WM_MENUCHAR | WM_NCACTIVATE | WM_SETFOCUS => true,
+        _ => false,
+    }
+}
+
+/// Destroys a window, through its MDI client if it's an MDI
+/// child so the frame's window menu is kept up to date
+pub(crate) fn destroy_window(hwnd: HWND) {
+    unsafe {
+        match (is_child(hwnd), GetParent(hwnd)) {
+            (true, Ok(client)) => {
+                SendMessageW(client, WM_MDIDESTROY, WPARAM(hwnd.0 as usize), LPARAM(0));
+            }
+            _ => {
+                let _ = DestroyWindow(hwnd);
+            }
+        }
+    }
+}
+
+pub(crate) fn client(frame: HWND) -> Option<HWND> {
+    CLIENTS.with(|clients| {
+        clients
+            .borrow()
+            .iter()
+            .find(|(hwnd, _)| *hwnd == frame.0 as isize)
+            .map(|(_, client)| HWND(*client as *mut c_void))
+    })
+}
+
+/// Creates the MDI client window filling `frame`, listing the
+/// children in the frame's menu bar's submenu at `window_menu`
+pub(crate) fn create_client(frame: HWND, window_menu: Option<usize>) -> Result<HWND> {
+    if let Some(client) = client(frame) {
+        return Ok(client);
+    }
+    let window_menu = match window_menu {
+        Some(position) => unsafe { GetSubMenu(GetMenu(frame), position as i32) },
+        None => HMENU::default(),
+    };
+    let create_struct = CLIENTCREATESTRUCT {
+        hWindowMenu: HANDLE(window_menu.0),
+        idFirstChild: FIRST_CHILD_ID,
+    };
+    let mut rect = RECT::default();
+    let client = unsafe {
+        let _ = GetClientRect(frame, &mut rect);
+        let style = GetWindowLongPtrW(frame, GWL_STYLE);
+        SetWindowLongPtrW(frame, GWL_STYLE, style | WS_CLIPCHILDREN.0 as isize);
+        CreateWindowExW(
+            WS_EX_CLIENTEDGE,
+            w!("MDICLIENT"),
+            None,
+            WS_CHILD | WS_CLIPCHILDREN | WS_VSCROLL | WS_HSCROLL | WS_VISIBLE,
+            0,
+            0,
+            rect.right,
+            rect.bottom,
+            frame,
+            None,
+            WPModule::new().get_hinstance(),
+            Some(&create_struct as *const _ as *const c_void),
+        )?
+    };
+    add_client(frame, client);
+    Ok(client)
+}
+
+/// Makes `client` the MDI client of `frame`
+pub(crate) fn add_client(frame: HWND, client: HWND) {
+    CLIENTS.with(|clients| {
+        clients
+            .borrow_mut()
+            .push((frame.0 as isize, client.0 as isize))
+    });
+}
+
+/// Forgets a frame as it's destroyed
+pub(crate) fn frame_destroyed(frame: HWND) {
+    CLIENTS.with(|clients| {
+        clients
+            .borrow_mut()
+            .retain(|(hwnd, _)| *hwnd != frame.0 as isize)
+    });
+}
+
+/// Opens and shows a document window of type `C` inside `frame`,
+/// which must have had MDI enabled
+pub fn open_child<C: Win>(
+    frame: HWND,
+    title: &str,
+    create_args: &WinCreateArgs,
+) -> Result<WinHandle<C>> {
+    let client = client(frame).ok_or(super::Error::InvalidCreateArgs(
+        "MDI hasn't been enabled on the frame window",
+    ))?;
+    let mut create_args = create_args.clone().with_parent(client);
+    create_args.ex_style |= WS_EX_MDICHILD;
+
+    let child = WinHandle::new(C::new(WPModule::new().get_hinstance()));
+    child.create_window_with_args(title, &create_args)?;
+    child.show();
+    child.update();
+    Ok(child)
+}
+
+/// Sends a message to `frame`'s MDI client, if it has one
+pub(crate) fn send_to_client(frame: HWND, message: u32, wparam: WPARAM) -> LRESULT {
+    match client(frame) {
+        Some(client) => unsafe { SendMessageW(client, message, wparam, LPARAM(0)) },
+        None => LRESULT(0),
+    }
+}
+
+/// Handles the MDI keyboard shortcuts, such as Ctrl+F4
+/// to close the active child, for every MDI frame
+pub(crate) fn translate_accelerator(msg: &MSG) -> bool {
+    let clients = CLIENTS.with(|clients| clients.borrow().clone());
+    clients.into_iter().any(|(_, client)| unsafe {
+        TranslateMDISysAccel(HWND(client as *mut c_void), msg).as_bool()
+    })
+}
@@ -0,0 +1,441 @@
+//! Menus built in code, as an alternative to a menu resource.
+//!
+//! A [`Menu`] becomes a window's menu bar with
+//! [`Win::set_menu_bar`](super::Win::set_menu_bar), or is shown as
+//! a context menu with
+//! [`Win::show_context_menu`](super::Win::show_context_menu).
+//!
+//! ```ignore
+//! let menu = Menu::new().with_submenu(
+//!     "&File",
+//!     Menu::new()
+//!         .with_item(MenuItem::command("&Open...", ID_OPEN).with_shortcut("Ctrl+O"))
+//!         .with_separator()
+//!         .with_item(MenuItem::action("E&xit", |win: &mut MyWindow| {
+//!             win.post_message(SendMessageParams::Close);
+//!         })),
+//! );
+//! win.set_menu_bar(Some(menu))?;
+//! ```
+//!
+//! Choosing an item runs its action, or else passes its id to
+//! `on_command`. Check items toggle, and radio items are selected,
+//! when they're chosen from the menu bar. A `&` in an item's text
+//! underlines the next letter as its mnemonic.
+
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
+use windows::{
+    core::*,
+    Win32::{Foundation::*, UI::WindowsAndMessaging::*},
+};
+
+use super::{
+    error::{Error, Result},
+    mdi,
+    win::Win,
+    win_callback, CommandEvent, EventHandled, SourceType, WinCallback,
+};
+
+/// Ids given to items with an action but no id of their own, up
+/// to [`mdi::FIRST_CHILD_ID`]
+pub const FIRST_ACTION_ID: u16 = 0xC000;
+
+// the item data that marks a check item, which Windows doesn't
+// otherwise tell apart from an item that's just ticked
+const CHECK_ITEM: usize = 1;
+
+enum MenuItemKind {
+    Normal,
+    Check,
+    Radio,
+    Separator,
+    Submenu(Menu),
+}
+
+/// An entry in a [`Menu`]
+pub struct MenuItem {
+    text: String,
+    shortcut: Option<String>,
+    kind: MenuItemKind,
+    id: Option<u16>,
+    action: Option<WinCallback>,
+    enabled: bool,
+    checked: bool,
+}
+
+impl MenuItem {
+    fn new(text: &str, kind: MenuItemKind) -> Self {
+        MenuItem {
+            text: text.to_string(),
+            shortcut: None,
+            kind,
+            id: None,
+            action: None,
+            enabled: true,
+            checked: false,
+        }
+    }
+
+    /// An item that sends `id` to `on_command` when chosen
+    pub fn command(text: &str, id: u16) -> Self {
+        MenuItem::new(text, MenuItemKind::Normal).with_id(id)
+    }
+
+    /// An item that runs `action` on the window when chosen
+    pub fn action<W, F>(text: &str, action: F) -> Self
+    where
+        W: Win,
+        F: FnMut(&mut W) + 'static,
+    {
+        MenuItem {
+            action: Some(win_callback(action)),
+            ..MenuItem::new(text, MenuItemKind::Normal)
+        }
+    }
+
+    /// Gives the item an id, so it can be enabled or checked later.
+    /// Items with an action get one from [`FIRST_ACTION_ID`] up if
+    /// they're not given one, skipping any ids given to other items.
+    /// Building a menu fails with `Error::DuplicateMenuId` if two
+    /// items are given the same id.
+    pub fn with_id(mut self, id: u16) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Shows a keyboard shortcut, e.g. `Ctrl+S`, on the right of
+    /// the item. The shortcut itself comes from an accelerator table.
+    pub fn with_shortcut(mut self, shortcut: &str) -> Self {
+        self.shortcut = Some(shortcut.to_string());
+        self
+    }
+
+    /// Makes the item show a tick, which toggles when it's chosen
+    pub fn with_check(mut self, checked: bool) -> Self {
+        self.kind = MenuItemKind::Check;
+        self.checked = checked;
+        self
+    }
+
+    /// Makes the item one of a group of radio items, which
+    /// is every radio item next to it in the menu
+    pub fn with_radio(mut self, checked: bool) -> Self {
+        self.kind = MenuItemKind::Radio;
+        self.checked = checked;
+        self
+    }
+
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+}
+
+/// A menu bar or popup menu, built up with `with_*` calls
+#[derive(Default)]
+pub struct Menu {
+    items: Vec<MenuItem>,
+}
+
+impl Menu {
+    pub fn new() -> Self {
+        Menu::default()
+    }
+
+    pub fn with_item(mut self, item: MenuItem) -> Self {
+        self.items.push(item);
+        self
+    }
+
+    pub fn with_separator(mut self) -> Self {
+        self.items.push(MenuItem::new("", MenuItemKind::Separator));
+        self
+    }
+
+    pub fn with_submenu(mut self, text: &str, submenu: Menu) -> Self {
+        self.items
+            .push(MenuItem::new(text, MenuItemKind::Submenu(submenu)));
+        self
+    }
+
+    /// Creates the native menu, moving the items' actions into
+    /// `actions` under their ids
+    pub(crate) fn build(
+        self,
+        popup: bool,
+        actions: &mut HashMap<u16, WinCallback>,
+    ) -> Result<HMENU> {
+        let mut taken = HashSet::new();
+        self.take_ids(&mut taken)?;
+        self.build_with(popup, actions, &taken)
+    }
+
+    /// Adds the ids items were given to `taken`, failing if
+    /// two items were given the same one
+    fn take_ids(&self, taken: &mut HashSet<u16>) -> Result<()> {
+        for item in &self.items {
+            if let Some(id) = item.id {
+                if !taken.insert(id) {
+                    return Err(Error::DuplicateMenuId(id));
+                }
+            }
+            if let MenuItemKind::Submenu(submenu) = &item.kind {
+                submenu.take_ids(taken)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn build_with(
+        self,
+        popup: bool,
+        actions: &mut HashMap<u16, WinCallback>,
+        taken: &HashSet<u16>,
+    ) -> Result<HMENU> {
+        let menu = unsafe {
+            match popup {
+                true => CreatePopupMenu()?,
+                false => CreateMenu()?,
+            }
+        };
+        if let Err(err) = self.append_items(menu, actions, taken) {
+            let _ = unsafe { DestroyMenu(menu) };
+            return Err(err);
+        }
+        Ok(menu)
+    }
+
+    fn append_items(
+        self,
+        menu: HMENU,
+        actions: &mut HashMap<u16, WinCallback>,
+        taken: &HashSet<u16>,
+    ) -> Result<()> {
+        for (position, item) in self.items.into_iter().enumerate() {
+            let text = match &item.shortcut {
+                Some(shortcut) => HSTRING::from(format!("{}\t{}", item.text, shortcut)),
+                None => HSTRING::from(&item.text),
+            };
+            let mut info = MENUITEMINFOW {
+                cbSize: mem::size_of::<MENUITEMINFOW>() as u32,
+                fMask: MIIM_FTYPE | MIIM_STATE | MIIM_ID | MIIM_STRING,
+                dwTypeData: PWSTR(text.as_ptr() as *mut u16),
+                ..Default::default()
+            };
+            if !item.enabled {
+                info.fState |= MFS_DISABLED;
+            }
+            if item.checked {
+                info.fState |= MFS_CHECKED;
+            }
+
+            let id = match (item.id, item.action) {
+                (id, None) => id.unwrap_or_default(),
+                (id, Some(action)) => {
+                    let id = match id {
+                        Some(id) => id,
+                        None => next_action_id(actions, taken)?,
+                    };
+                    actions.insert(id, action);
+                    id
+                }
+            };
+            info.wID = id as u32;
+
+            match item.kind {
+                MenuItemKind::Normal => {}
+                MenuItemKind::Check => {
+                    info.fMask |= MIIM_DATA;
+                    info.dwItemData = CHECK_ITEM;
+                }
+                MenuItemKind::Radio => info.fType |= MFT_RADIOCHECK,
+                MenuItemKind::Separator => {
+                    info.fMask = MIIM_FTYPE;
+                    info.fType = MFT_SEPARATOR;
+                }
+                MenuItemKind::Submenu(submenu) => {
+                    info.fMask |= MIIM_SUBMENU;
+                    info.hSubMenu = submenu.build_with(true, actions, taken)?;
+                }
+            }
+
+            let inserted = unsafe { InsertMenuItemW(menu, position as u32, true, &info) };
+            if let Err(err) = inserted {
+                if !info.hSubMenu.is_invalid() {
+                    let _ = unsafe { DestroyMenu(info.hSubMenu) };
+                }
+                return Err(err.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn next_action_id(actions: &HashMap<u16, WinCallback>, taken: &HashSet<u16>) -> Result<u16> {
+    // the ids above are for the MDI children in the window menu
+    (FIRST_ACTION_ID..mdi::FIRST_CHILD_ID as u16)
+        .find(|id| !actions.contains_key(id) && !taken.contains(id))
+        .ok_or(Error::IdsExhausted("menu actions"))
+}
+
+/// Replaces the window's menu bar, destroying the old one
+pub(crate) fn set_menu_bar<W: Win>(win: &mut W, menu: Option<Menu>) -> Result<()> {
+    let hwnd = win.get_hwnd();
+    let mut actions = HashMap::new();
+    let new_menu = match menu {
+        Some(menu) => menu.build(false, &mut actions)?,
+        None => HMENU::default(),
+    };
+    unsafe {
+        let old_menu = GetMenu(hwnd);
+        if let Err(err) = SetMenu(hwnd, new_menu) {
+            if !new_menu.is_invalid() {
+                let _ = DestroyMenu(new_menu);
+            }
+            return Err(err.into());
+        }
+        if !old_menu.is_invalid() {
+            let _ = DestroyMenu(old_menu);
+        }
+    }
+    win.get_base().menu_actions = actions;
+    Ok(())
+}
+
+/// Shows `menu` at `x`, `y` in screen coordinates until an item
+/// is chosen or it's dismissed, then runs the chosen item
+pub(crate) fn show_context_menu<W: Win>(win: &mut W, menu: Menu, x: i32, y: i32) -> Result<()> {
+    let mut actions = HashMap::new();
+    let menu = menu.build(true, &mut actions)?;
+    let id = unsafe {
+        let id = TrackPopupMenuEx(
+            menu,
+            (TPM_RETURNCMD | TPM_RIGHTBUTTON).0,
+            x,
+            y,
+            win.get_hwnd(),
+            None,
+        );
+        let _ = DestroyMenu(menu);
+        id.0 as u16
+    };
+    match (id, actions.remove(&id)) {
+        // dismissed
+        (0, _) => {}
+        (_, Some(mut action)) => action(win),
+        (id, None) => {
+            win.on_command(&CommandEvent {
+                command: id as i32,
+                source_type: SourceType::Menu,
+                control_hwnd: None,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Handles a menu bar item or accelerator being chosen, updating
+/// check and radio items and running the item's action if it has one
+pub(crate) fn command<W: Win>(win: &mut W, id: u16) -> Option<EventHandled> {
+    let menu = unsafe { GetMenu(win.get_hwnd()) };
+    if !menu.is_invalid() {
+        if let Some((submenu, position)) = find(menu, id) {
+            let info = item_info(submenu, position);
+            if info.fType.contains(MFT_RADIOCHECK) {
+                check_radio(submenu, position);
+            } else if info.dwItemData == CHECK_ITEM {
+                set_checked(menu, id, !info.fState.contains(MFS_CHECKED));
+            }
+        }
+    }
+
+    let mut action = win.get_base().menu_actions.remove(&id)?;
+    action(win);
+    // unless the action replaced the menu bar, whose actions are now the new menu's
+    if unsafe { GetMenu(win.get_hwnd()) } == menu {
+        win.get_base().menu_actions.insert(id, action);
+    }
+    Some(EventHandled::Handled(LRESULT(0)))
+}
+
+pub(crate) fn set_enabled(menu: HMENU, id: u16, enabled: bool) {
+    let flags = match enabled {
+        true => MF_BYCOMMAND | MF_ENABLED,
+        false => MF_BYCOMMAND | MF_GRAYED,
+    };
+    unsafe {
+        let _ = EnableMenuItem(menu, id as u32, flags);
+    }
+}
+
+/// Ticks or unticks an item, selecting it from its group
+/// if it's a radio item
+pub(crate) fn set_checked(menu: HMENU, id: u16, checked: bool) {
+    if let Some((submenu, position)) = find(menu, id) {
+        if checked && item_info(submenu, position).fType.contains(MFT_RADIOCHECK) {
+            check_radio(submenu, position);
+            return;
+        }
+    }
+    let flags = match checked {
+        true => MF_BYCOMMAND | MF_CHECKED,
+        false => MF_BYCOMMAND | MF_UNCHECKED,
+    };
+    unsafe {
+        CheckMenuItem(menu, id as u32, flags.0);
+    }
+}
+
+pub(crate) fn is_checked(menu: HMENU, id: u16) -> bool {
+    let state = unsafe { GetMenuState(menu, id as u32, MF_BYCOMMAND) };
+    state != u32::MAX && state & MF_CHECKED.0 != 0
+}
+
+/// Checks the radio item at `position`, unchecking
+/// the radio items next to it
+fn check_radio(menu: HMENU, position: u32) {
+    let is_radio = |position: u32| item_info(menu, position).fType.contains(MFT_RADIOCHECK);
+    let count = unsafe { GetMenuItemCount(menu) }.max(0) as u32;
+    let mut first = position;
+    while first > 0 && is_radio(first - 1) {
+        first -= 1;
+    }
+    let mut last = position;
+    while last + 1 < count && is_radio(last + 1) {
+        last += 1;
+    }
+    unsafe {
+        let _ = CheckMenuRadioItem(menu, first, last, position, MF_BYPOSITION.0);
+    }
+}
+
+fn item_info(menu: HMENU, position: u32) -> MENUITEMINFOW {
+    let mut info = MENUITEMINFOW {
+        cbSize: mem::size_of::<MENUITEMINFOW>() as u32,
+        fMask: MIIM_FTYPE | MIIM_STATE | MIIM_DATA,
+        ..Default::default()
+    };
+    unsafe {
+        let _ = GetMenuItemInfoW(menu, position, true, &mut info);
+    }
+    info
+}
+
+/// The menu, or submenu, holding the item with `id`, and its position
+fn find(menu: HMENU, id: u16) -> Option<(HMENU, u32)> {
+    let count = unsafe { GetMenuItemCount(menu) };
+    for position in 0..count.max(0) {
+        let submenu = unsafe { GetSubMenu(menu, position) };
+        if !submenu.is_invalid() {
+            if let Some(found) = find(submenu, id) {
+                return Some(found);
+            }
+        } else if unsafe { GetMenuItemID(menu, position) } == id as u32 {
+            return Some((menu, position as u32));
+        }
+    }
+    None
+}
@@ -0,0 +1,134 @@
+//! The monitors attached to the desktop.
+//!
+//! [`monitors`] lists them, and [`Win::monitor`](super::Win::monitor)
+//! finds the one a window is on. Windows are told when monitors
+//! are added, removed or rearranged through
+//! [`Win::on_display_change`](super::Win::on_display_change).
+
+use std::mem;
+use windows::Win32::{
+    Foundation::*,
+    Graphics::Gdi::*,
+    UI::{HiDpi::*, WindowsAndMessaging::MONITORINFOF_PRIMARY},
+};
+
+use super::{
+    dpi::{self, DEFAULT_DPI},
+    error::Result,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Monitor {
+    pub handle: HMONITOR,
+    /// The device name, e.g. `\\.\DISPLAY1`
+    pub name: String,
+    /// The whole monitor, in screen coordinates
+    pub rect: RECT,
+    /// The monitor less the taskbar and any docked toolbars
+    pub work_area: RECT,
+    pub dpi: u32,
+    pub primary: bool,
+}
+
+impl Monitor {
+    pub fn from_handle(handle: HMONITOR) -> Result<Self> {
+        let info = monitor_info(handle)?;
+        Ok(Monitor {
+            handle,
+            name: device_name(&info),
+            rect: info.monitorInfo.rcMonitor,
+            work_area: info.monitorInfo.rcWork,
+            dpi: effective_dpi(handle),
+            primary: info.monitorInfo.dwFlags & MONITORINFOF_PRIMARY != 0,
+        })
+    }
+
+    /// The monitor `hwnd` is on, or the nearest one if it's off screen
+    pub fn from_window(hwnd: HWND) -> Result<Self> {
+        Monitor::from_handle(unsafe { MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST) })
+    }
+
+    /// The monitor containing a point in screen coordinates,
+    /// or the nearest one
+    pub fn from_point(x: i32, y: i32) -> Result<Self> {
+        let point = POINT { x, y };
+        Monitor::from_handle(unsafe { MonitorFromPoint(point, MONITOR_DEFAULTTONEAREST) })
+    }
+
+    /// How much bigger than at 96 DPI things are drawn, e.g. `1.5` at 144 DPI
+    pub fn scale_factor(&self) -> f32 {
+        dpi::scale_factor(self.dpi)
+    }
+}
+
+/// Every monitor on the desktop, with the primary one first
+pub fn monitors() -> Vec<Monitor> {
+    let mut monitors: Vec<Monitor> = handles()
+        .into_iter()
+        .filter_map(|handle| Monitor::from_handle(handle).ok())
+        .collect();
+    monitors.sort_by_key(|monitor| !monitor.primary);
+    monitors
+}
+
+/// The monitor with the taskbar and the start menu
+pub fn primary() -> Result<Monitor> {
+    Monitor::from_point(0, 0)
+}
+
+/// The monitor's effective DPI. Unless the app is DPI aware this
+/// is always 96, as Windows scales the app's windows itself,
+/// see [`dpi`](super::dpi).
+fn effective_dpi(monitor: HMONITOR) -> u32 {
+    let (mut x, mut y) = (0, 0);
+    match unsafe { GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut x, &mut y) } {
+        Ok(()) => x,
+        Err(_) => DEFAULT_DPI,
+    }
+}
+
+pub(crate) fn monitor_info(monitor: HMONITOR) -> Result<MONITORINFOEXW> {
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = mem::size_of::<MONITORINFOEXW>() as u32;
+    unsafe {
+        GetMonitorInfoW(monitor, &mut info.monitorInfo).ok()?;
+    }
+    Ok(info)
+}
+
+pub(crate) fn device_name(info: &MONITORINFOEXW) -> String {
+    let len = info.szDevice.iter().position(|c| *c == 0);
+    String::from_utf16_lossy(&info.szDevice[..len.unwrap_or(info.szDevice.len())])
+}
+
+pub(crate) fn find(name: &str) -> Option<HMONITOR> {
+    handles().into_iter().find(|monitor| {
+        monitor_info(*monitor)
+            .map(|info| device_name(&info) == name)
+            .unwrap_or(false)
+    })
+}
+
+fn handles() -> Vec<HMONITOR> {
+    unsafe extern "system" fn collect(
+        monitor: HMONITOR,
+        _hdc: HDC,
+        _rect: *mut RECT,
+        monitors: LPARAM,
+    ) -> BOOL {
+        let monitors = &mut *(monitors.0 as *mut Vec<HMONITOR>);
+        monitors.push(monitor);
+        TRUE
+    }
+
+    let mut monitors: Vec<HMONITOR> = Vec::new();
+    unsafe {
+        let _ = EnumDisplayMonitors(
+            None,
+            None,
+            Some(collect),
+            LPARAM(&mut monitors as *mut _ as isize),
+        );
+    }
+    monitors
+}
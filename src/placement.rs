@@ -0,0 +1,143 @@
+//! Saving and restoring where a window is on screen, so an
+//! app can reopen its windows where the user left them.
+//!
+//! With the `serde` feature [`WindowPlacement`] can be
+//! serialized alongside the rest of an app's settings.
+
+use std::mem;
+use windows::Win32::{Foundation::*, Graphics::Gdi::*, UI::WindowsAndMessaging::*};
+
+use super::{
+    error::Result,
+    monitor::{self, device_name, monitor_info},
+};
+
+/// Where a window is, as returned by [`Win::placement`](super::Win::placement)
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowPlacement {
+    /// The window's restored bounds in screen coordinates,
+    /// even while it's maximized or minimized
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub maximized: bool,
+    /// The device name of the monitor the window was on,
+    /// e.g. `\\.\DISPLAY1`
+    pub monitor: String,
+}
+
+impl WindowPlacement {
+    fn rect(&self) -> RECT {
+        RECT {
+            left: self.x,
+            top: self.y,
+            right: self.x + self.width,
+            bottom: self.y + self.height,
+        }
+    }
+}
+
+pub(crate) fn get(hwnd: HWND) -> Result<WindowPlacement> {
+    let mut placement = WINDOWPLACEMENT {
+        length: mem::size_of::<WINDOWPLACEMENT>() as u32,
+        ..Default::default()
+    };
+    let info = unsafe {
+        GetWindowPlacement(hwnd, &mut placement)?;
+        monitor_info(MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST))?
+    };
+
+    let rect = workspace_to_screen(placement.rcNormalPosition, &info.monitorInfo);
+    let maximized = placement.showCmd == SW_SHOWMAXIMIZED.0 as u32
+        || (placement.showCmd == SW_SHOWMINIMIZED.0 as u32
+            && placement.flags.contains(WPF_RESTORETOMAXIMIZED));
+    Ok(WindowPlacement {
+        x: rect.left,
+        y: rect.top,
+        width: rect.right - rect.left,
+        height: rect.bottom - rect.top,
+        maximized,
+        monitor: device_name(&info),
+    })
+}
+
+/// Moves the window to `placement`, keeping it hidden if it
+/// isn't visible yet. If the saved monitor has gone, or the
+/// window would be off screen, it's moved onto the nearest
+/// monitor's work area instead.
+pub(crate) fn set(hwnd: HWND, placement: &WindowPlacement) -> Result<()> {
+    let rect = placement.rect();
+    let saved_monitor = monitor::find(&placement.monitor);
+    let on_screen = !unsafe { MonitorFromRect(&rect, MONITOR_DEFAULTTONULL) }.is_invalid();
+
+    let monitor = match saved_monitor {
+        Some(monitor) if on_screen => monitor,
+        _ => unsafe { MonitorFromRect(&rect, MONITOR_DEFAULTTONEAREST) },
+    };
+    let info = monitor_info(monitor)?;
+    let rect = match saved_monitor.is_some() && on_screen {
+        true => rect,
+        false => clamp_to_area(rect, &info.monitorInfo.rcWork),
+    };
+
+    let visible = unsafe { IsWindowVisible(hwnd) }.as_bool();
+    let show_cmd = match (visible, placement.maximized) {
+        (false, _) => SW_HIDE,
+        (true, true) => SW_SHOWMAXIMIZED,
+        (true, false) => SW_SHOWNORMAL,
+    };
+    let placement = WINDOWPLACEMENT {
+        length: mem::size_of::<WINDOWPLACEMENT>() as u32,
+        showCmd: show_cmd.0 as u32,
+        rcNormalPosition: screen_to_workspace(rect, &info.monitorInfo),
+        ..Default::default()
+    };
+    unsafe {
+        SetWindowPlacement(hwnd, &placement)?;
+    }
+    Ok(())
+}
+
+/// Shrinks `rect` to fit inside `area` if it's too big,
+/// then moves it as little as possible to be inside it
+pub fn clamp_to_area(rect: RECT, area: &RECT) -> RECT {
+    let width = (rect.right - rect.left).min(area.right - area.left);
+    let height = (rect.bottom - rect.top).min(area.bottom - area.top);
+    let left = rect.left.clamp(area.left, area.right - width);
+    let top = rect.top.clamp(area.top, area.bottom - height);
+    RECT {
+        left,
+        top,
+        right: left + width,
+        bottom: top + height,
+    }
+}
+
+// `WINDOWPLACEMENT` uses workspace coordinates, which are offset
+// from screen coordinates by any taskbar at the top or left
+fn workspace_to_screen(rect: RECT, info: &MONITORINFO) -> RECT {
+    offset(
+        rect,
+        info.rcWork.left - info.rcMonitor.left,
+        info.rcWork.top - info.rcMonitor.top,
+    )
+}
+
+fn screen_to_workspace(rect: RECT, info: &MONITORINFO) -> RECT {
+    offset(
+        rect,
+        info.rcMonitor.left - info.rcWork.left,
+        info.rcMonitor.top - info.rcWork.top,
+    )
+}
+
+fn offset(rect: RECT, x: i32, y: i32) -> RECT {
+    RECT {
+        left: rect.left + x,
+        top: rect.top + y,
+        right: rect.right + x,
+        bottom: rect.bottom + y,
+    }
+}
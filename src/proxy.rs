@@ -0,0 +1,115 @@
+use std::{
+    any::Any,
+    marker::PhantomData,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+};
+use windows::Win32::{Foundation::*, UI::WindowsAndMessaging::*};
+
+use super::{
+    error::{Error, Result},
+    WM_USER_EVENT,
+};
+
+type Payload = Box<dyn Any + Send>;
+
+// the sender takes the payload back out if the window can't be woken
+type Slot = Arc<Mutex<Option<Payload>>>;
+
+/// A handle for sending payloads of type `T` to a window from other
+/// threads. Each payload is delivered to [`Win::on_user_event`] on
+/// the UI thread.
+///
+/// [`Win::on_user_event`]: crate::Win::on_user_event
+pub struct WinProxy<T> {
+    hwnd: isize,
+    sender: Sender<Slot>,
+    payload: PhantomData<fn(T)>,
+}
+
+impl<T: Send + 'static> WinProxy<T> {
+    /// Queues `payload` for the window, failing with `Error::WindowClosed`
+    /// if the window hasn't been created yet or has been destroyed
+    pub fn send(&self, payload: T) -> Result<()> {
+        // a null window would post the wake to this thread instead
+        if self.hwnd == 0 {
+            return Err(Error::WindowClosed);
+        }
+        let slot: Slot = Arc::new(Mutex::new(Some(Box::new(payload))));
+        if self.sender.send(slot.clone()).is_err() {
+            return Err(Error::WindowClosed);
+        }
+        let posted =
+            unsafe { PostMessageW(HWND(self.hwnd as _), WM_USER_EVENT, WPARAM(0), LPARAM(0)) };
+        if posted.is_err() {
+            // unless an earlier wake has already delivered it
+            let undelivered = slot.lock().map(|mut slot| slot.take()).unwrap_or_default();
+            if undelivered.is_some() {
+                return Err(Error::WindowClosed);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> Clone for WinProxy<T> {
+    fn clone(&self) -> Self {
+        Self {
+            hwnd: self.hwnd,
+            sender: self.sender.clone(),
+            payload: PhantomData,
+        }
+    }
+}
+
+/// A payload sent through a [`WinProxy`]
+#[derive(Debug)]
+pub struct UserEvent(Payload);
+
+impl UserEvent {
+    pub fn is<T: 'static>(&self) -> bool {
+        self.0.is::<T>()
+    }
+
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+
+    pub fn downcast<T: 'static>(self) -> std::result::Result<T, Self> {
+        self.0.downcast().map(|payload| *payload).map_err(Self)
+    }
+}
+
+/// The receiving end of a window's proxies, created
+/// the first time a proxy is asked for
+#[derive(Default)]
+pub(crate) struct UserEvents {
+    channel: Option<(Sender<Slot>, Receiver<Slot>)>,
+}
+
+impl UserEvents {
+    pub fn proxy<T>(&mut self, hwnd: HWND) -> WinProxy<T> {
+        let (sender, _) = self.channel.get_or_insert_with(channel);
+        WinProxy {
+            hwnd: hwnd.0 as isize,
+            sender: sender.clone(),
+            payload: PhantomData,
+        }
+    }
+
+    pub fn next(&self) -> Option<UserEvent> {
+        let (_, receiver) = self.channel.as_ref()?;
+        // skipping payloads taken back by senders that failed to post
+        receiver.try_iter().find_map(|slot| {
+            let payload = slot.lock().map(|mut slot| slot.take());
+            payload.ok().flatten().map(UserEvent)
+        })
+    }
+
+    /// Disconnects every proxy, so later sends fail
+    pub fn close(&mut self) {
+        self.channel = None;
+    }
+}
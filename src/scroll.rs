@@ -0,0 +1,260 @@
+//! Scrolling a window's client area over bigger content.
+//!
+//! A window becomes scrollable once it's given a content size with
+//! [`Win::set_content_size`](super::Win::set_content_size). Its
+//! scroll bars are then kept in step with its size, and move the
+//! content for the scroll bar arrows, tracks and thumbs and the
+//! mouse wheel. `on_paint` draws in content coordinates, and is
+//! passed the part of the content that's visible.
+//!
+//! Sizes and offsets are in the same units as `on_paint`, so are
+//! logical units for a window using
+//! [`Win::set_logical_units`](super::Win::set_logical_units).
+
+use std::mem;
+use windows::Win32::{
+    Foundation::*,
+    Graphics::Gdi::{InvalidateRect, HRGN},
+    UI::{Controls::SetScrollInfo, WindowsAndMessaging::*},
+};
+
+use super::{dpi, hword, lword, win::Win, Event, EventHandled};
+
+/// What `SPI_GETWHEELSCROLLLINES` gives when the wheel scrolls by pages
+const WHEEL_PAGESCROLL: u32 = u32::MAX;
+
+/// A scrollable window's content and where it's scrolled to
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Scroll {
+    pub(crate) content: (i32, i32),
+    pub(crate) offset: (i32, i32),
+    /// How far the arrows and the mouse wheel scroll, or
+    /// `None` for a line of the window's text
+    pub(crate) line: Option<(i32, i32)>,
+    /// The client area at the last update
+    pub(crate) page: (i32, i32),
+}
+
+impl Scroll {
+    fn max_offset(&self) -> (i32, i32) {
+        (
+            (self.content.0 - self.page.0).max(0),
+            (self.content.1 - self.page.1).max(0),
+        )
+    }
+
+    fn clamp(&self, x: i32, y: i32) -> (i32, i32) {
+        let (max_x, max_y) = self.max_offset();
+        (x.clamp(0, max_x), y.clamp(0, max_y))
+    }
+}
+
+/// Where a scroll bar command moves the offset to
+pub(crate) fn scroll_position(
+    command: SCROLLBAR_COMMAND,
+    offset: i32,
+    track: i32,
+    line: i32,
+    page: i32,
+    max: i32,
+) -> i32 {
+    let position = match command {
+        SB_LINEUP => offset - line,
+        SB_LINEDOWN => offset + line,
+        SB_PAGEUP => offset - page,
+        SB_PAGEDOWN => offset + page,
+        SB_THUMBTRACK | SB_THUMBPOSITION => track,
+        SB_TOP => 0,
+        SB_BOTTOM => max,
+        _ => offset,
+    };
+    position.clamp(0, max.max(0))
+}
+
+/// The client area's size in the units the window paints in
+fn page<W: Win>(win: &mut W) -> (i32, i32) {
+    let rect = win.get_client_rect().unwrap_or_default();
+    let (width, height) = (rect.right - rect.left, rect.bottom - rect.top);
+    match win.base().chrome.logical_units.get() {
+        true => {
+            let dpi = win.dpi();
+            (dpi::to_logical(width, dpi), dpi::to_logical(height, dpi))
+        }
+        false => (width, height),
+    }
+}
+
+/// Brings the scroll bars into line with the content and the size
+/// of the client area, which may move the content back into view
+pub(crate) fn update_bars(hwnd: HWND, scroll: &mut Scroll, page: (i32, i32)) {
+    scroll.page = page;
+    scroll.offset = scroll.clamp(scroll.offset.0, scroll.offset.1);
+    for (bar, content, page, offset) in [
+        (SB_HORZ, scroll.content.0, page.0, scroll.offset.0),
+        (SB_VERT, scroll.content.1, page.1, scroll.offset.1),
+    ] {
+        let info = SCROLLINFO {
+            cbSize: mem::size_of::<SCROLLINFO>() as u32,
+            fMask: SIF_RANGE | SIF_PAGE | SIF_POS,
+            nMin: 0,
+            nMax: content - 1,
+            nPage: page.max(0) as u32,
+            nPos: offset,
+            ..Default::default()
+        };
+        unsafe {
+            SetScrollInfo(hwnd, bar, &info, true);
+        }
+    }
+}
+
+pub(crate) fn set_content_size<W: Win>(win: &mut W, width: i32, height: i32) {
+    let page = page(win);
+    let hwnd = win.get_hwnd();
+    let base = win.get_base();
+    let scroll = base.scroll.get_or_insert_with(Scroll::default);
+    scroll.content = (width.max(0), height.max(0));
+    update_bars(hwnd, scroll, page);
+    unsafe {
+        let _ = InvalidateRect(hwnd, None, true);
+    }
+}
+
+/// Moves the content to `x`, `y`, scrolling what's already been
+/// drawn and repainting the part that's come into view
+pub(crate) fn scroll_to<W: Win>(win: &mut W, x: i32, y: i32) {
+    let hwnd = win.get_hwnd();
+    let logical_dpi = match win.base().chrome.logical_units.get() {
+        true => Some(win.dpi()),
+        false => None,
+    };
+    let Some(scroll) = &mut win.get_base().scroll else {
+        return;
+    };
+    let (x, y) = scroll.clamp(x, y);
+    let (old_x, old_y) = scroll.offset;
+    if (old_x, old_y) == (x, y) {
+        return;
+    }
+    scroll.offset = (x, y);
+
+    // the offsets are scaled rather than the difference, so what's
+    // moved lines up with what's drawn at the new offset
+    let to_physical = |offset| match logical_dpi {
+        Some(dpi) => dpi::to_physical(offset, dpi),
+        None => offset,
+    };
+    let dx = to_physical(old_x) - to_physical(x);
+    let dy = to_physical(old_y) - to_physical(y);
+    unsafe {
+        for (bar, offset) in [(SB_HORZ, x), (SB_VERT, y)] {
+            let info = SCROLLINFO {
+                cbSize: mem::size_of::<SCROLLINFO>() as u32,
+                fMask: SIF_POS,
+                nPos: offset,
+                ..Default::default()
+            };
+            SetScrollInfo(hwnd, bar, &info, true);
+        }
+        ScrollWindowEx(
+            hwnd,
+            dx,
+            dy,
+            None,
+            None,
+            HRGN::default(),
+            None,
+            SW_INVALIDATE | SW_ERASE,
+        );
+    }
+}
+
+fn line_size<W: Win>(win: &mut W, scroll: &Scroll) -> (i32, i32) {
+    scroll.line.unwrap_or_else(|| {
+        let tm = &win.get_base().tm;
+        (
+            tm.tmAveCharWidth.max(1),
+            (tm.tmHeight + tm.tmExternalLeading).max(1),
+        )
+    })
+}
+
+/// Handles `WM_HSCROLL` and `WM_VSCROLL` from the window's own scroll bars
+pub(crate) fn on_scroll<W: Win>(win: &mut W, event: &Event) -> EventHandled {
+    let Some(scroll) = win.get_base().scroll else {
+        return EventHandled::NotHandled;
+    };
+    // from a scroll bar control rather than the window's own
+    if event.lparam.0 != 0 {
+        return EventHandled::NotHandled;
+    }
+    let vertical = event.message == WM_VSCROLL;
+    let bar = if vertical { SB_VERT } else { SB_HORZ };
+
+    let mut info = SCROLLINFO {
+        cbSize: mem::size_of::<SCROLLINFO>() as u32,
+        fMask: SIF_TRACKPOS,
+        ..Default::default()
+    };
+    let _ = unsafe { GetScrollInfo(event.hwnd, bar, &mut info) };
+
+    let command = SCROLLBAR_COMMAND(lword(event.wparam.0 as isize));
+    let line = line_size(win, &scroll);
+    let max = scroll.max_offset();
+    let (x, y) = scroll.offset;
+    let (x, y) = match vertical {
+        true => (
+            x,
+            scroll_position(command, y, info.nTrackPos, line.1, scroll.page.1, max.1),
+        ),
+        false => (
+            scroll_position(command, x, info.nTrackPos, line.0, scroll.page.0, max.0),
+            y,
+        ),
+    };
+    scroll_to(win, x, y);
+    EventHandled::Handled(LRESULT(0))
+}
+
+/// Handles `WM_MOUSEWHEEL` and `WM_MOUSEHWHEEL`, leaving it to
+/// the parent window if there's nowhere to scroll to
+pub(crate) fn on_wheel<W: Win>(win: &mut W, event: &Event) -> EventHandled {
+    let Some(scroll) = win.get_base().scroll else {
+        return EventHandled::NotHandled;
+    };
+    let vertical = event.message == WM_MOUSEWHEEL;
+    let max = scroll.max_offset();
+    if (vertical && max.1 == 0) || (!vertical && max.0 == 0) {
+        return EventHandled::NotHandled;
+    }
+
+    let mut lines: u32 = 3;
+    let _ = unsafe {
+        SystemParametersInfoW(
+            SPI_GETWHEELSCROLLLINES,
+            0,
+            Some(&mut lines as *mut u32 as *mut _),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+    };
+    let delta = hword(event.wparam.0 as isize) as i16 as i32;
+    let line = line_size(win, &scroll);
+    let (line, page) = match vertical {
+        true => (line.1, scroll.page.1),
+        false => (line.0, scroll.page.0),
+    };
+    let notch = match lines {
+        WHEEL_PAGESCROLL => page as i64,
+        lines => lines as i64 * line as i64,
+    };
+    let distance =
+        (delta as i64 * notch / WHEEL_DELTA as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+    let (x, y) = scroll.offset;
+    match vertical {
+        // wheeling up, a positive delta, moves towards the top
+        true => scroll_to(win, x, y.saturating_sub(distance)),
+        // tilting right, a positive delta, moves towards the right
+        false => scroll_to(win, x.saturating_add(distance), y),
+    }
+    EventHandled::Handled(LRESULT(0))
+}
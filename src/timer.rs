@@ -0,0 +1,159 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    mem,
+    rc::{Rc, Weak},
+    time::Duration,
+};
+use windows::Win32::{Foundation::*, UI::WindowsAndMessaging::*};
+
+use super::{
+    error::{Error, Result},
+    win::Win,
+    EventHandled, WinCallback,
+};
+
+/// A handle to a timer started with [`Win::set_timer`] or
+/// [`Win::set_timeout`]. Cancelling the handle stops the
+/// timer and its callback will not be called again.
+#[derive(Clone, Debug)]
+pub struct TimerHandle {
+    hwnd: HWND,
+    id: usize,
+    cancelled: Rc<Cell<bool>>,
+    timers: Weak<RefCell<HashMap<usize, Timer>>>,
+}
+
+impl TimerHandle {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn cancel(&self) {
+        if !self.cancelled.replace(true) {
+            unsafe {
+                let _ = KillTimer(self.hwnd, self.id);
+            }
+            if let Some(timers) = self.timers.upgrade() {
+                // dropped outside the borrow, as the callback may own handles
+                let timer = timers.borrow_mut().remove(&self.id);
+                drop(timer);
+            }
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.cancelled.get()
+    }
+}
+
+struct Timer {
+    callback: Option<WinCallback>,
+    repeat: bool,
+    cancelled: Rc<Cell<bool>>,
+}
+
+/// The timers belonging to a window, keyed by the
+/// id passed to `SetTimer`
+pub(crate) struct Timers {
+    // shared with the handles, so cancelling drops the callback
+    timers: Rc<RefCell<HashMap<usize, Timer>>>,
+    next_id: usize,
+}
+
+impl Default for Timers {
+    fn default() -> Self {
+        Self {
+            timers: Rc::default(),
+            next_id: 1,
+        }
+    }
+}
+
+impl Timers {
+    pub fn start(
+        &mut self,
+        hwnd: HWND,
+        interval: Duration,
+        repeat: bool,
+        callback: WinCallback,
+    ) -> Result<TimerHandle> {
+        // a null window would get a thread timer with an id of its own
+        if hwnd.is_invalid() {
+            return Err(Error::WindowClosed);
+        }
+        let id = self.next_id;
+        let elapse = interval.as_millis().min(u32::MAX as u128) as u32;
+        if unsafe { SetTimer(hwnd, id, elapse, None) } == 0 {
+            return Err(Error::Backend(windows::core::Error::from_win32()));
+        }
+        self.next_id += 1;
+
+        let cancelled = Rc::new(Cell::new(false));
+        self.timers.borrow_mut().insert(
+            id,
+            Timer {
+                callback: Some(callback),
+                repeat,
+                cancelled: cancelled.clone(),
+            },
+        );
+        Ok(TimerHandle {
+            hwnd,
+            id,
+            cancelled,
+            timers: Rc::downgrade(&self.timers),
+        })
+    }
+
+    /// Kills every timer and drops the callbacks, used
+    /// when the window is destroyed
+    pub fn clear(&mut self, hwnd: HWND) {
+        let timers = mem::take(&mut *self.timers.borrow_mut());
+        for (id, timer) in timers {
+            if !timer.cancelled.replace(true) {
+                unsafe {
+                    let _ = KillTimer(hwnd, id);
+                }
+            }
+        }
+    }
+}
+
+/// Runs the callback for a `WM_TIMER` message. The callback is
+/// taken out of the window while it runs so it can freely
+/// borrow the window, start new timers or cancel itself.
+pub(crate) fn fire<W: Win>(win: &mut W, id: usize) -> EventHandled {
+    let hwnd = win.get_hwnd();
+    let timers = win.get_base().timers.timers.clone();
+
+    let mut entries = timers.borrow_mut();
+    let (mut callback, repeat) = match entries.get_mut(&id) {
+        None => return EventHandled::NotHandled,
+        Some(timer) => match timer.callback.take() {
+            // already running, e.g. a nested message loop in the callback
+            None => return EventHandled::Handled(LRESULT(0)),
+            Some(callback) => (callback, timer.repeat),
+        },
+    };
+
+    if !repeat {
+        if let Some(timer) = entries.remove(&id) {
+            timer.cancelled.set(true);
+        }
+        unsafe {
+            let _ = KillTimer(hwnd, id);
+        }
+    }
+    drop(entries);
+
+    callback(win);
+
+    // unless it cancelled itself while it ran
+    if repeat {
+        if let Some(timer) = timers.borrow_mut().get_mut(&id) {
+            timer.callback = Some(callback);
+        }
+    }
+    EventHandled::Handled(LRESULT(0))
+}
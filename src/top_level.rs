@@ -0,0 +1,116 @@
+//! Tracks the app's top-level windows, so more can be opened
+//! while the app is running and the message loop ends according
+//! to the app's [`QuitPolicy`].
+
+use std::{cell::RefCell, mem};
+use windows::Win32::{Foundation::*, UI::WindowsAndMessaging::*};
+
+use super::{error::Result, handle::WinHandle, win::Win, win_create_args::WinCreateArgs, WPModule};
+
+/// Decides when closing windows ends the app
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum QuitPolicy {
+    /// Quit when the app's main window is closed
+    #[default]
+    MainWindowClosed,
+    /// Quit once every top-level window has been closed
+    LastWindowClosed,
+    /// Only quit when [`quit`] is called
+    Explicit,
+}
+
+#[derive(Default)]
+struct Registry {
+    policy: QuitPolicy,
+    main: isize,
+    open: Vec<isize>,
+}
+
+thread_local! {
+    static REGISTRY: RefCell<Registry> = RefCell::new(Registry::default());
+}
+
+pub fn set_quit_policy(policy: QuitPolicy) {
+    REGISTRY.with(|registry| registry.borrow_mut().policy = policy);
+}
+
+pub fn quit_policy() -> QuitPolicy {
+    REGISTRY.with(|registry| registry.borrow().policy)
+}
+
+/// Ends the message loop, whatever the quit policy
+pub fn quit(exit_code: i32) {
+    unsafe {
+        PostQuitMessage(exit_code);
+    }
+}
+
+/// The number of top-level windows currently open
+pub fn window_count() -> usize {
+    REGISTRY.with(|registry| registry.borrow().open.len())
+}
+
+/// Opens and shows a new top-level window of type `W` using
+/// the default main window arguments. The window keeps itself
+/// alive until it's destroyed, so the handle can be dropped.
+pub fn open_window<W: Win>(title: &str) -> Result<WinHandle<W>> {
+    open_window_with_args(title, &WinCreateArgs::default_win_main())
+}
+
+/// Opens and shows a new top-level window of type `W`
+pub fn open_window_with_args<W: Win>(
+    title: &str,
+    create_args: &WinCreateArgs,
+) -> Result<WinHandle<W>> {
+    let win = WinHandle::new(W::new(WPModule::new().get_hinstance()));
+    let hwnd = win.create_window_with_args(title, create_args)?;
+    win.show();
+    win.update();
+
+    REGISTRY.with(|registry| registry.borrow_mut().open.push(hwnd.0 as isize));
+    Ok(win)
+}
+
+pub(crate) fn register_main(hwnd: HWND) {
+    REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        registry.main = hwnd.0 as isize;
+        registry.open.push(hwnd.0 as isize);
+    });
+}
+
+/// Called as each window is destroyed, posting `WM_QUIT`
+/// if the quit policy says the app is done
+pub(crate) fn window_destroyed(hwnd: HWND) {
+    let hwnd = hwnd.0 as isize;
+    let quit_now = REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        let Some(index) = registry.open.iter().position(|open| *open == hwnd) else {
+            return false;
+        };
+        registry.open.remove(index);
+
+        match registry.policy {
+            QuitPolicy::MainWindowClosed => hwnd == registry.main,
+            QuitPolicy::LastWindowClosed => registry.open.is_empty(),
+            QuitPolicy::Explicit => false,
+        }
+    });
+
+    if quit_now {
+        quit(0);
+    }
+}
+
+/// Destroys every window that's still open once the message
+/// loop has finished, so their state is dropped. They're forgotten
+/// first, so destroying them can't post another `WM_QUIT` that
+/// would end the next message loop straight away.
+pub(crate) fn close_all() {
+    let open = REGISTRY.with(|registry| mem::take(&mut registry.borrow_mut().open));
+    for hwnd in open {
+        unsafe {
+            let _ = DestroyWindow(HWND(hwnd as _));
+        }
+    }
+}
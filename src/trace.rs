@@ -0,0 +1,162 @@
+//! Opt-in tracing of every message dispatched to a window.
+//!
+//! When enabled each message is logged at `trace` level under the
+//! `window_of_opportunity::messages` target with a readable name,
+//! its decoded parameters (e.g. `WM_SIZE 800x600`) and whether one
+//! of the window's handlers dealt with it.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use windows::Win32::UI::{Controls::WM_MOUSELEAVE, WindowsAndMessaging::*};
+
+use super::{hword, lword, Event};
+
+static TRACING: AtomicBool = AtomicBool::new(false);
+
+pub fn set_message_tracing(enabled: bool) {
+    TRACING.store(enabled, Ordering::Relaxed);
+}
+
+pub fn message_tracing() -> bool {
+    TRACING.load(Ordering::Relaxed)
+}
+
+pub(crate) fn trace_message(event: &Event, handled: bool) {
+    if !message_tracing()
+        || !log::log_enabled!(target: "window_of_opportunity::messages", log::Level::Trace)
+    {
+        return;
+    }
+    log::trace!(
+        target: "window_of_opportunity::messages",
+        "{:?} {} {}",
+        event.hwnd.0,
+        describe(event),
+        if handled { "handled" } else { "default" }
+    );
+}
+
+/// The message's name followed by any parameters worth showing
+pub fn describe(event: &Event) -> String {
+    let name = message_name(event.message);
+    let lparam = event.lparam.0;
+    let wparam = event.wparam.0;
+
+    match event.message {
+        WM_SIZE => format!("{} {}x{}", name, lword(lparam), hword(lparam)),
+        WM_MOVE => format!(
+            "{} ({}, {})",
+            name,
+            lword(lparam) as i16,
+            hword(lparam) as i16
+        ),
+        WM_MOUSEMOVE | WM_LBUTTONDOWN | WM_LBUTTONUP | WM_LBUTTONDBLCLK | WM_RBUTTONDOWN
+        | WM_RBUTTONUP | WM_RBUTTONDBLCLK | WM_MBUTTONDOWN | WM_MBUTTONUP | WM_MBUTTONDBLCLK => {
+            format!(
+                "{} ({}, {})",
+                name,
+                lword(lparam) as i16,
+                hword(lparam) as i16
+            )
+        }
+        WM_KEYDOWN | WM_KEYUP | WM_SYSKEYDOWN | WM_SYSKEYUP => {
+            format!("{} vk=0x{:02X}", name, wparam)
+        }
+        WM_CHAR | WM_DEADCHAR | WM_SYSCHAR => match char::from_u32(wparam as u32) {
+            Some(c) if !c.is_control() => format!("{} '{}'", name, c),
+            _ => format!("{} 0x{:04X}", name, wparam),
+        },
+        WM_COMMAND => format!(
+            "{} id={} code={}",
+            name,
+            lword(wparam as isize),
+            hword(wparam as isize)
+        ),
+        WM_TIMER => format!("{} id={}", name, wparam),
+        _ => name,
+    }
+}
+
+/// A readable name for a message, e.g. `WM_PAINT` or `WM_APP+3`
+pub fn message_name(message: u32) -> String {
+    let name = match message {
+        WM_NULL => "WM_NULL",
+        WM_CREATE => "WM_CREATE",
+        WM_DESTROY => "WM_DESTROY",
+        WM_MOVE => "WM_MOVE",
+        WM_SIZE => "WM_SIZE",
+        WM_ACTIVATE => "WM_ACTIVATE",
+        WM_SETFOCUS => "WM_SETFOCUS",
+        WM_KILLFOCUS => "WM_KILLFOCUS",
+        WM_ENABLE => "WM_ENABLE",
+        WM_SETREDRAW => "WM_SETREDRAW",
+        WM_SETTEXT => "WM_SETTEXT",
+        WM_GETTEXT => "WM_GETTEXT",
+        WM_GETTEXTLENGTH => "WM_GETTEXTLENGTH",
+        WM_PAINT => "WM_PAINT",
+        WM_CLOSE => "WM_CLOSE",
+        WM_QUIT => "WM_QUIT",
+        WM_ERASEBKGND => "WM_ERASEBKGND",
+        WM_SHOWWINDOW => "WM_SHOWWINDOW",
+        WM_SETTINGCHANGE => "WM_SETTINGCHANGE",
+        WM_ACTIVATEAPP => "WM_ACTIVATEAPP",
+        WM_SETCURSOR => "WM_SETCURSOR",
+        WM_MOUSEACTIVATE => "WM_MOUSEACTIVATE",
+        WM_GETMINMAXINFO => "WM_GETMINMAXINFO",
+        WM_WINDOWPOSCHANGING => "WM_WINDOWPOSCHANGING",
+        WM_WINDOWPOSCHANGED => "WM_WINDOWPOSCHANGED",
+        WM_NOTIFY => "WM_NOTIFY",
+        WM_GETICON => "WM_GETICON",
+        WM_SETICON => "WM_SETICON",
+        WM_NCCREATE => "WM_NCCREATE",
+        WM_NCDESTROY => "WM_NCDESTROY",
+        WM_NCCALCSIZE => "WM_NCCALCSIZE",
+        WM_NCHITTEST => "WM_NCHITTEST",
+        WM_NCPAINT => "WM_NCPAINT",
+        WM_NCACTIVATE => "WM_NCACTIVATE",
+        WM_NCMOUSEMOVE => "WM_NCMOUSEMOVE",
+        WM_NCLBUTTONDOWN => "WM_NCLBUTTONDOWN",
+        WM_NCLBUTTONUP => "WM_NCLBUTTONUP",
+        WM_NCLBUTTONDBLCLK => "WM_NCLBUTTONDBLCLK",
+        WM_KEYDOWN => "WM_KEYDOWN",
+        WM_KEYUP => "WM_KEYUP",
+        WM_CHAR => "WM_CHAR",
+        WM_DEADCHAR => "WM_DEADCHAR",
+        WM_SYSKEYDOWN => "WM_SYSKEYDOWN",
+        WM_SYSKEYUP => "WM_SYSKEYUP",
+        WM_SYSCHAR => "WM_SYSCHAR",
+        WM_COMMAND => "WM_COMMAND",
+        WM_SYSCOMMAND => "WM_SYSCOMMAND",
+        WM_TIMER => "WM_TIMER",
+        WM_HSCROLL => "WM_HSCROLL",
+        WM_VSCROLL => "WM_VSCROLL",
+        WM_INITMENU => "WM_INITMENU",
+        WM_INITMENUPOPUP => "WM_INITMENUPOPUP",
+        WM_MENUSELECT => "WM_MENUSELECT",
+        WM_ENTERIDLE => "WM_ENTERIDLE",
+        WM_MOUSEMOVE => "WM_MOUSEMOVE",
+        WM_LBUTTONDOWN => "WM_LBUTTONDOWN",
+        WM_LBUTTONUP => "WM_LBUTTONUP",
+        WM_LBUTTONDBLCLK => "WM_LBUTTONDBLCLK",
+        WM_RBUTTONDOWN => "WM_RBUTTONDOWN",
+        WM_RBUTTONUP => "WM_RBUTTONUP",
+        WM_RBUTTONDBLCLK => "WM_RBUTTONDBLCLK",
+        WM_MBUTTONDOWN => "WM_MBUTTONDOWN",
+        WM_MBUTTONUP => "WM_MBUTTONUP",
+        WM_MBUTTONDBLCLK => "WM_MBUTTONDBLCLK",
+        WM_MOUSEWHEEL => "WM_MOUSEWHEEL",
+        WM_MOUSEHWHEEL => "WM_MOUSEHWHEEL",
+        WM_PARENTNOTIFY => "WM_PARENTNOTIFY",
+        WM_SIZING => "WM_SIZING",
+        WM_CAPTURECHANGED => "WM_CAPTURECHANGED",
+        WM_MOVING => "WM_MOVING",
+        WM_ENTERSIZEMOVE => "WM_ENTERSIZEMOVE",
+        WM_EXITSIZEMOVE => "WM_EXITSIZEMOVE",
+        WM_MOUSELEAVE => "WM_MOUSELEAVE",
+        WM_DPICHANGED => "WM_DPICHANGED",
+        WM_DISPLAYCHANGE => "WM_DISPLAYCHANGE",
+        WM_USER..WM_APP => return format!("WM_USER+{}", message - WM_USER),
+        WM_APP..=0xBFFF => return format!("WM_APP+{}", message - WM_APP),
+        _ => return format!("0x{:04X}", message),
+    };
+    name.to_string()
+}
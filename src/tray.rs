@@ -0,0 +1,262 @@
+//! Icons in the notification area (the system tray).
+//!
+//! A [`TrayIcon`] belongs to a window, which receives its clicks
+//! through [`Win::on_tray_event`](super::Win::on_tray_event). The
+//! icon is removed when the `TrayIcon` is dropped or its window is
+//! destroyed, whichever comes first.
+
+use std::{
+    cell::{Cell, RefCell},
+    mem,
+};
+use windows::Win32::{
+    Foundation::*,
+    UI::{Shell::*, WindowsAndMessaging::*},
+};
+
+use super::{
+    dialog::MessageBoxIcon,
+    error::{Error, Result},
+    hword, lword, Event, WM_TRAY,
+};
+
+const NIN_KEYSELECT: u32 = NIN_SELECT | 1;
+
+thread_local! {
+    // (hwnd, id) of every icon that's currently shown
+    static ICONS: RefCell<Vec<(isize, u32)>> = const { RefCell::new(Vec::new()) };
+    // the callback carries the id in 16 bits
+    static NEXT_ID: Cell<u16> = const { Cell::new(1) };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrayEventKind {
+    /// Clicked, or selected with the keyboard
+    Click,
+    DoubleClick,
+    /// Right clicked, or the context menu key was pressed
+    RightClick,
+    BalloonClicked,
+    BalloonTimedOut,
+}
+
+/// Something the user did to one of a window's tray icons
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrayEvent {
+    /// The [`TrayIcon::id`] of the icon
+    pub id: u32,
+    pub kind: TrayEventKind,
+    /// Where it happened, in screen coordinates
+    pub x: i32,
+    pub y: i32,
+}
+
+/// An entry in a tray icon's context menu
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrayMenuItem<'a> {
+    /// Returned from [`TrayIcon::show_context_menu`] when chosen
+    Command(u16, &'a str),
+    Separator,
+}
+
+/// An icon shown in the notification area
+#[derive(Debug)]
+pub struct TrayIcon {
+    hwnd: HWND,
+    id: u32,
+}
+
+impl TrayIcon {
+    /// Adds an icon owned by `owner`
+    pub fn add(owner: HWND, icon: HICON, tooltip: &str) -> Result<Self> {
+        let id = next_id()?;
+        let tray_icon = TrayIcon { hwnd: owner, id };
+
+        let mut data = tray_icon.data();
+        data.uFlags = NIF_MESSAGE | NIF_ICON | NIF_TIP | NIF_SHOWTIP;
+        data.uCallbackMessage = WM_TRAY;
+        data.hIcon = icon;
+        copy_wide(&mut data.szTip, tooltip);
+        notify(NIM_ADD, &data)?;
+
+        data.Anonymous.uVersion = NOTIFYICON_VERSION_4;
+        if let Err(err) = notify(NIM_SETVERSION, &data) {
+            let _ = notify(NIM_DELETE, &data);
+            return Err(err);
+        }
+        ICONS.with(|icons| icons.borrow_mut().push((owner.0 as isize, id)));
+        Ok(tray_icon)
+    }
+
+    /// Tells this icon's events apart from the window's other icons
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn set_icon(&self, icon: HICON) -> Result<()> {
+        let mut data = self.data();
+        data.uFlags = NIF_ICON;
+        data.hIcon = icon;
+        notify(NIM_MODIFY, &data)
+    }
+
+    pub fn set_tooltip(&self, tooltip: &str) -> Result<()> {
+        let mut data = self.data();
+        data.uFlags = NIF_TIP | NIF_SHOWTIP;
+        copy_wide(&mut data.szTip, tooltip);
+        notify(NIM_MODIFY, &data)
+    }
+
+    /// Shows a balloon notification from the icon. Whether it's
+    /// clicked or times out is passed to `on_tray_event`.
+    pub fn show_balloon(&self, title: &str, text: &str, icon: MessageBoxIcon) -> Result<()> {
+        let mut data = self.data();
+        data.uFlags = NIF_INFO;
+        copy_wide(&mut data.szInfoTitle, title);
+        copy_wide(&mut data.szInfo, text);
+        data.dwInfoFlags = match icon {
+            MessageBoxIcon::None => NIIF_NONE,
+            MessageBoxIcon::Info | MessageBoxIcon::Question => NIIF_INFO,
+            MessageBoxIcon::Warning => NIIF_WARNING,
+            MessageBoxIcon::Error => NIIF_ERROR,
+        };
+        notify(NIM_MODIFY, &data)
+    }
+
+    /// Shows a context menu at `x`, `y`, usually where a
+    /// `RightClick` happened, and returns the id of the chosen
+    /// item, or `None` if it was dismissed. The choice isn't sent
+    /// as a `WM_COMMAND`, so it can't be mistaken for a menu bar item.
+    pub fn show_context_menu(&self, items: &[TrayMenuItem], x: i32, y: i32) -> Result<Option<u16>> {
+        unsafe {
+            let menu = CreatePopupMenu()?;
+            for item in items {
+                let appended = match item {
+                    TrayMenuItem::Command(id, text) => AppendMenuW(
+                        menu,
+                        MF_STRING,
+                        *id as usize,
+                        &windows::core::HSTRING::from(*text),
+                    ),
+                    TrayMenuItem::Separator => AppendMenuW(menu, MF_SEPARATOR, 0, None),
+                };
+                if let Err(err) = appended {
+                    let _ = DestroyMenu(menu);
+                    return Err(err.into());
+                }
+            }
+
+            // without this the menu doesn't close when clicking elsewhere
+            let _ = SetForegroundWindow(self.hwnd);
+            let id = TrackPopupMenuEx(
+                menu,
+                (TPM_RETURNCMD | TPM_NONOTIFY | TPM_RIGHTBUTTON | TPM_BOTTOMALIGN).0,
+                x,
+                y,
+                self.hwnd,
+                None,
+            );
+            let _ = PostMessageW(self.hwnd, WM_NULL, WPARAM(0), LPARAM(0));
+            let _ = DestroyMenu(menu);
+            match id.0 {
+                0 => Ok(None),
+                id => Ok(Some(id as u16)),
+            }
+        }
+    }
+
+    fn data(&self) -> NOTIFYICONDATAW {
+        NOTIFYICONDATAW {
+            cbSize: mem::size_of::<NOTIFYICONDATAW>() as u32,
+            hWnd: self.hwnd,
+            uID: self.id,
+            ..Default::default()
+        }
+    }
+}
+
+impl Drop for TrayIcon {
+    fn drop(&mut self) {
+        let shown = ICONS.with(|icons| {
+            let mut icons = icons.borrow_mut();
+            let index = icons
+                .iter()
+                .position(|icon| *icon == (self.hwnd.0 as isize, self.id));
+            index.map(|index| icons.remove(index)).is_some()
+        });
+        if shown {
+            let _ = notify(NIM_DELETE, &self.data());
+        }
+    }
+}
+
+/// The next id that isn't in use, wrapping round to reuse freed ones
+fn next_id() -> Result<u32> {
+    ICONS.with(|icons| {
+        let icons = icons.borrow();
+        NEXT_ID.with(|next| {
+            for _ in 0..u16::MAX {
+                let id = next.get();
+                next.set(id.checked_add(1).unwrap_or(1));
+                if !icons.iter().any(|(_, shown)| *shown == id as u32) {
+                    return Ok(id as u32);
+                }
+            }
+            Err(Error::IdsExhausted("tray icons"))
+        })
+    })
+}
+
+fn notify(message: NOTIFY_ICON_MESSAGE, data: &NOTIFYICONDATAW) -> Result<()> {
+    match unsafe { Shell_NotifyIconW(message, data) }.as_bool() {
+        true => Ok(()),
+        false => Err(Error::Backend(windows::core::Error::from_win32())),
+    }
+}
+
+/// Copies as much of `text` as fits, leaving room for the terminator
+fn copy_wide(dst: &mut [u16], text: &str) {
+    let max = dst.len() - 1;
+    let mut len = 0;
+    for (dst, c) in dst.iter_mut().zip(text.encode_utf16().take(max)) {
+        *dst = c;
+        len += 1;
+    }
+    dst[len] = 0;
+}
+
+/// Removes a window's icons as it's destroyed, as
+/// they can't be removed once its `HWND` has gone
+pub(crate) fn remove_all(hwnd: HWND) {
+    let ids: Vec<u32> = ICONS.with(|icons| {
+        let mut icons = icons.borrow_mut();
+        let ids = icons
+            .iter()
+            .filter(|icon| icon.0 == hwnd.0 as isize)
+            .map(|icon| icon.1)
+            .collect();
+        icons.retain(|icon| icon.0 != hwnd.0 as isize);
+        ids
+    });
+    for id in ids {
+        let _ = notify(NIM_DELETE, &TrayIcon { hwnd, id }.data());
+    }
+}
+
+/// Decodes the `WM_TRAY` callback message
+pub(crate) fn event(event: &Event) -> Option<TrayEvent> {
+    let kind = match lword(event.lparam.0) as u32 {
+        NIN_SELECT | NIN_KEYSELECT => TrayEventKind::Click,
+        WM_LBUTTONDBLCLK => TrayEventKind::DoubleClick,
+        WM_CONTEXTMENU => TrayEventKind::RightClick,
+        NIN_BALLOONUSERCLICK => TrayEventKind::BalloonClicked,
+        NIN_BALLOONTIMEOUT => TrayEventKind::BalloonTimedOut,
+        _ => return None,
+    };
+    Some(TrayEvent {
+        id: hword(event.lparam.0) as u32,
+        kind,
+        x: lword(event.wparam.0 as isize) as i16 as i32,
+        y: hword(event.wparam.0 as isize) as i16 as i32,
+    })
+}
@@ -1,33 +1,54 @@
 use crate::default_win_impl;
 
 use super::{
-    dc::DeviceContext, hword, kbd::KbdEvent, load_icon, lword, mouse::MouseEvent,
-    win_create_args::WinCreateArgs, BaseWin, CommandEvent, Event, EventHandled, SendMessageParams,
-    SourceType,
+    child,
+    chrome::{self, HitTest},
+    cursor::{self, Cursor},
+    dc::{Brush, DeviceContext},
+    dialog::{self, MessageBoxButtons, MessageBoxIcon, MessageBoxResult},
+    dpi,
+    error::{self, Error, Result},
+    executor,
+    frame::FrameStats,
+    handle::{self, WinHandle},
+    hword,
+    icon::{Icon, IconSize},
+    kbd::KbdEvent,
+    layered, lword, mdi,
+    menu::{self, Menu},
+    monitor::{self, Monitor},
+    mouse::MouseEvent,
+    placement::{self, WindowPlacement},
+    proxy::{UserEvent, WinProxy},
+    scroll,
+    timer::{self, TimerHandle},
+    top_level, trace,
+    tray::{self, TrayEvent, TrayIcon},
+    win_callback,
+    win_create_args::WinCreateArgs,
+    BaseWin, CloseRequest, CommandEvent, Event, EventHandled, SendMessageParams, SourceType,
+    Windowed, WM_EXECUTOR_UPDATE, WM_EXECUTOR_WAKE, WM_TRAY, WM_USER_EVENT,
 };
-use std::mem;
+use std::{future::Future, mem, time::Duration};
 use windows::{
     core::*,
     Win32::{Foundation::*, Graphics::Gdi::*, UI::WindowsAndMessaging::*},
 };
 
-pub trait Win {
+pub trait Win: Sized + 'static {
     fn new(inst: HINSTANCE) -> Self;
     fn get_hwnd(&self) -> HWND;
     fn set_hwnd(&mut self, hwnd: HWND);
-    fn get_canary(&self) -> i32 {
-        10
-    }
     fn get_base(&mut self) -> &mut BaseWin;
-    fn to_self_ptr(c_void: *mut ::core::ffi::c_void) -> *mut Self;
-    fn raw_ptr_isize(ptr: *mut Self) -> isize;
+    /// The base state, for queries through a shared borrow
+    fn base(&self) -> &BaseWin;
     fn show(&self) -> bool {
         unsafe {
             // SW_MAXIMIZE
             ShowWindow(self.get_hwnd(), SW_NORMAL).as_bool()
         }
     }
-    fn set_child(&mut self, child: Component);
+    fn set_child(&mut self, child: Component) -> Result<()>;
 
     fn update(&self) -> bool {
         unsafe { UpdateWindow(self.get_hwnd()).as_bool() }
@@ -41,6 +62,71 @@ pub trait Win {
         false
     }
 
+    /// Called by `WPApp::run` for each frame when the app has
+    /// been given a `FrameTiming`
+    fn on_frame(&mut self, _delta: Duration, _stats: &FrameStats) {}
+
+    /// Calls `callback` every `interval` until the returned
+    /// handle is cancelled or the window is destroyed
+    fn set_timer<F>(&mut self, interval: Duration, callback: F) -> Result<TimerHandle>
+    where
+        F: FnMut(&mut Self) + 'static,
+    {
+        let hwnd = self.get_hwnd();
+        self.get_base()
+            .timers
+            .start(hwnd, interval, true, win_callback(callback))
+    }
+
+    /// Calls `callback` once after `delay`, unless the returned
+    /// handle is cancelled or the window is destroyed first
+    fn set_timeout<F>(&mut self, delay: Duration, callback: F) -> Result<TimerHandle>
+    where
+        F: FnOnce(&mut Self) + 'static,
+    {
+        let hwnd = self.get_hwnd();
+        let mut callback = Some(callback);
+        self.get_base().timers.start(
+            hwnd,
+            delay,
+            false,
+            win_callback(move |win: &mut Self| {
+                if let Some(callback) = callback.take() {
+                    callback(win);
+                }
+            }),
+        )
+    }
+
+    /// Runs `future` on the UI thread's executor. When it completes
+    /// the closure it resolves to is called with the window, so state
+    /// can be updated after an `.await`. The update is dropped if the
+    /// window has been destroyed in the meantime. Fails with
+    /// `Error::WindowClosed` if the window hasn't been created yet.
+    fn spawn_local<F, U>(&self, future: F) -> Result<()>
+    where
+        F: Future<Output = U> + 'static,
+        U: FnOnce(&mut Self) + 'static,
+    {
+        let hwnd = self.get_hwnd();
+        // a null window would post the update to the thread, where it'd never run
+        if hwnd.is_invalid() {
+            return Err(Error::WindowClosed);
+        }
+        executor::spawn_local(async move {
+            let mut update = Some(future.await);
+            executor::post_update(
+                hwnd,
+                win_callback(move |win: &mut Self| {
+                    if let Some(update) = update.take() {
+                        update(win);
+                    }
+                }),
+            );
+        });
+        Ok(())
+    }
+
     fn on_paint(&self, _hdc: &mut DeviceContext, _rect: &mut RECT) -> EventHandled {
         EventHandled::NotHandled
     }
@@ -49,12 +135,10 @@ pub trait Win {
         EventHandled::NotHandled
     }
 
+    /// Whether destroying the window ends the app is
+    /// decided by the app's `QuitPolicy`
     fn on_destroy(&self, _event: &Event) -> EventHandled {
-        println!("WM_DESTROY");
-        unsafe {
-            PostQuitMessage(0);
-        }
-        EventHandled::Handled(LRESULT(0))
+        EventHandled::NotHandled
     }
 
     fn on_resize(&mut self, _x: i32, _y: i32) -> EventHandled {
@@ -62,10 +146,10 @@ pub trait Win {
     }
 
     fn on_command(&self, event: &CommandEvent) -> EventHandled {
-        println!("command... {:?}", event);
+        log::debug!("command {:?}", event);
         match event.command {
             100 => {
-                self.send_message(SendMessageParams::Close);
+                self.post_message(SendMessageParams::Close);
                 EventHandled::Handled(LRESULT(0))
             }
             _ => EventHandled::NotHandled,
@@ -80,16 +164,162 @@ pub trait Win {
         EventHandled::NotHandled
     }
 
-    fn send_message(&self, message: SendMessageParams) {
-        let (msg, wparam, lparam) = match message {
-            SendMessageParams::Close => (WM_CLOSE, WPARAM(0), LPARAM(0)),
+    /// Called on the UI thread for each payload sent through
+    /// one of this window's proxies
+    fn on_user_event(&mut self, _event: UserEvent) -> EventHandled {
+        EventHandled::NotHandled
+    }
+
+    /// Called when one of the window's tray icons is clicked or
+    /// its balloon notification is clicked or times out
+    fn on_tray_event(&mut self, _event: TrayEvent) -> EventHandled {
+        EventHandled::NotHandled
+    }
+
+    /// Adds an icon to the notification area whose events come to
+    /// this window, see [`tray`](super::tray)
+    fn add_tray_icon(&self, icon: HICON, tooltip: &str) -> Result<TrayIcon> {
+        TrayIcon::add(self.get_hwnd(), icon, tooltip)
+    }
+
+    /// Creates a proxy that other threads can use to send
+    /// payloads to this window
+    fn create_proxy<T: Send + 'static>(&mut self) -> WinProxy<T> {
+        let hwnd = self.get_hwnd();
+        self.get_base().user_events.proxy(hwnd)
+    }
+
+    /// Creates and shows a child window of type `C` inside this
+    /// one, see [`child`](super::child)
+    fn create_child<C: Win>(
+        &self,
+        title: &str,
+        create_args: &WinCreateArgs,
+    ) -> Result<WinHandle<C>> {
+        child::create_child(self.get_hwnd(), title, create_args)
+    }
+
+    /// The window's child windows, from the top of the z-order down
+    fn child_windows(&self) -> Vec<HWND> {
+        child::children(self.get_hwnd())
+    }
+
+    /// Moves the window above its siblings
+    fn bring_to_front(&self) -> Result<()> {
+        child::set_z_order(self.get_hwnd(), HWND_TOP)
+    }
+
+    /// Moves the window below its siblings
+    fn send_to_back(&self) -> Result<()> {
+        child::set_z_order(self.get_hwnd(), HWND_BOTTOM)
+    }
+
+    /// Makes the window an MDI frame, listing its children in the
+    /// submenu of its menu bar at `window_menu` if it's given. The
+    /// menu bar should be set first. A frame that handles
+    /// `on_resize` lays out the MDI client itself. See [`mdi`](super::mdi).
+    fn enable_mdi(&mut self, window_menu: Option<usize>) -> Result<()> {
+        mdi::create_client(self.get_hwnd(), window_menu)?;
+        Ok(())
+    }
+
+    /// Opens a document window of type `C` inside this MDI frame
+    fn open_mdi_child<C: Win>(&self, title: &str) -> Result<WinHandle<C>> {
+        mdi::open_child(self.get_hwnd(), title, &WinCreateArgs::default_mdi_child())
+    }
+
+    fn cascade_mdi_children(&self) {
+        mdi::send_to_client(self.get_hwnd(), WM_MDICASCADE, WPARAM(0));
+    }
+
+    /// Tiles the MDI children side by side, or one above the
+    /// other if `horizontal`
+    fn tile_mdi_children(&self, horizontal: bool) {
+        let how = match horizontal {
+            true => MDITILE_HORIZONTAL,
+            false => MDITILE_VERTICAL,
         };
+        mdi::send_to_client(self.get_hwnd(), WM_MDITILE, WPARAM(how.0 as usize));
+    }
+
+    /// Lines up the minimized MDI children along the bottom of the frame
+    fn arrange_mdi_icons(&self) {
+        mdi::send_to_client(self.get_hwnd(), WM_MDIICONARRANGE, WPARAM(0));
+    }
+
+    /// The MDI child that has the focus, if there are any
+    fn active_mdi_child(&self) -> Option<HWND> {
+        let active = mdi::send_to_client(self.get_hwnd(), WM_MDIGETACTIVE, WPARAM(0));
+        let active = HWND(active.0 as *mut std::ffi::c_void);
+        (!active.is_invalid()).then_some(active)
+    }
+
+    /// Replaces the window's menu bar, or removes it with `None`,
+    /// see [`menu`](super::menu)
+    fn set_menu_bar(&mut self, menu: Option<Menu>) -> Result<()> {
+        menu::set_menu_bar(self, menu)
+    }
+
+    /// Shows a popup menu at `x`, `y` in screen coordinates and
+    /// runs the item that's chosen, if any, before returning
+    fn show_context_menu(&mut self, menu: Menu, x: i32, y: i32) -> Result<()> {
+        menu::show_context_menu(self, menu, x, y)
+    }
+
+    /// Enables or greys out the menu bar item with `id`
+    fn enable_menu_item(&self, id: u16, enabled: bool) {
+        menu::set_enabled(unsafe { GetMenu(self.get_hwnd()) }, id, enabled);
+    }
+
+    /// Ticks or unticks the menu bar item with `id`. Checking
+    /// a radio item unchecks the rest of its group.
+    fn check_menu_item(&self, id: u16, checked: bool) {
+        menu::set_checked(unsafe { GetMenu(self.get_hwnd()) }, id, checked);
+    }
+
+    fn is_menu_item_checked(&self, id: u16) -> bool {
+        menu::is_checked(unsafe { GetMenu(self.get_hwnd()) }, id)
+    }
+
+    /// Shows a message box modal to this window
+    fn message_box(
+        &self,
+        text: &str,
+        caption: &str,
+        icon: MessageBoxIcon,
+        buttons: MessageBoxButtons,
+    ) -> MessageBoxResult {
+        dialog::message_box(Some(self.get_hwnd()), text, caption, icon, buttons)
+    }
+
+    /// Called when the user (or `SendMessageParams::Close`) asks
+    /// for the window to close. Returning `CloseRequest::Cancel`
+    /// keeps the window open, e.g. after prompting to save changes.
+    fn on_close_requested(&mut self) -> CloseRequest {
+        CloseRequest::Close
+    }
+
+    /// Sends the message and waits for it to be handled. From one of
+    /// the window's own handlers it's only handled once that returns.
+    #[deprecated(note = "use `post_message`, which doesn't re-enter the window")]
+    fn send_message(&self, message: SendMessageParams) {
+        let (msg, wparam, lparam) = message.params();
 
         unsafe {
             SendMessageW(self.get_hwnd(), msg, wparam, lparam);
         }
     }
 
+    /// Posts the message and returns straight away, so it's handled
+    /// once the current handler returns instead of re-entering the window
+    fn post_message(&self, message: SendMessageParams) {
+        let (msg, wparam, lparam) = message.params();
+
+        unsafe {
+            let _ = PostMessageW(self.get_hwnd(), msg, wparam, lparam);
+        }
+    }
+
     fn on_ncdestroy(&self, _event: &super::Event) -> EventHandled {
         EventHandled::NotHandled
     }
@@ -102,84 +332,395 @@ pub trait Win {
         Ok(rect)
     }
 
-    fn create_window(&mut self, title: PCWSTR) -> Result<HWND>;
-    fn create_window_with_args(
-        &mut self,
-        title: PCWSTR,
-        create_args: &WinCreateArgs,
-    ) -> Result<HWND>;
+    /// The whole window, including its frame, in screen coordinates
+    fn get_window_rect(&self) -> Result<RECT> {
+        let mut rect = RECT::default();
+        unsafe {
+            GetWindowRect(self.get_hwnd(), &mut rect)?;
+        }
+        Ok(rect)
+    }
 
-    fn create_win(
-        &mut self,
-        title: PCWSTR,
-        create_args: &WinCreateArgs,
-        instance: HINSTANCE,
-    ) -> std::result::Result<HWND, Error> {
-        let hwnd: std::result::Result<HWND, Error>;
+    fn set_title(&self, title: &str) -> Result<()> {
+        unsafe {
+            SetWindowTextW(self.get_hwnd(), &HSTRING::from(title))?;
+        }
+        Ok(())
+    }
 
-        let class_name = create_args.class_name;
+    fn title(&self) -> String {
+        let hwnd = self.get_hwnd();
+        unsafe {
+            let len = GetWindowTextLengthW(hwnd);
+            let mut text = vec![0; len as usize + 1];
+            let len = GetWindowTextW(hwnd, &mut text);
+            String::from_utf16_lossy(&text[..len as usize])
+        }
+    }
 
-        let hinst = instance;
-        let brush: HGDIOBJ;
+    /// Moves the window's top left corner, in screen coordinates for
+    /// a top-level window or its parent's client coordinates for a
+    /// child window
+    fn set_position(&self, x: i32, y: i32) -> Result<()> {
         unsafe {
-            brush = GetStockObject(WHITE_BRUSH);
+            SetWindowPos(
+                self.get_hwnd(),
+                None,
+                x,
+                y,
+                0,
+                0,
+                SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE,
+            )?;
         }
-        let brush = HBRUSH(brush.0);
+        Ok(())
+    }
 
-        let icon = match create_args.icon {
-            None => load_icon(create_args.instance, IDI_APPLICATION),
-            Some(icon_name) => load_icon(create_args.instance, icon_name),
-        };
-        let icon = match icon {
-            Err(_) => HICON::default(),
-            Ok(icon) => icon,
-        };
+    fn position(&self) -> Result<(i32, i32)> {
+        let rect = self.get_window_rect()?;
+        Ok((rect.left, rect.top))
+    }
 
+    /// Resizes the whole window, including its frame
+    fn set_size(&self, width: i32, height: i32) -> Result<()> {
         unsafe {
-            let wc = WNDCLASSEXW {
-                hCursor: create_args.cursor,
-                hIcon: icon,
-                hInstance: create_args.instance,
-                lpszClassName: create_args.class_name,
-                hbrBackground: brush,
-                style: CS_HREDRAW | CS_VREDRAW,
-                lpfnWndProc: Some(Self::wndproc),
-                cbSize: mem::size_of::<WNDCLASSEXW>() as u32,
-                lpszMenuName: create_args.menu_name,
-                ..Default::default()
-            };
-
-            // if class doesn't already exist? check the result for this...
-            let atom = RegisterClassExW(&wc);
-            debug_assert!(atom != 0);
-
-            hwnd = CreateWindowExW(
-                create_args.ex_style, // | WS_EX_LAYERED,
-                // WS_EX_APPWINDOW | WS_EX_TOPMOST,
-                // & !(WS_EX_DLGMODALFRAME |
-                //     WS_EX_WINDOWEDGE | WS_EX_CLIENTEDGE | WS_EX_STATICEDGE),
-                class_name,
-                title,
-                create_args.style,
-                // | WS_VSCROLL | WS_HSCROLL,
-                CW_USEDEFAULT,
-                CW_USEDEFAULT,
-                create_args.window_width,
-                create_args.window_height,
-                None,
+            SetWindowPos(
+                self.get_hwnd(),
                 None,
-                hinst,
-                Some(self as *const _ as _),
+                0,
+                0,
+                width,
+                height,
+                SWP_NOMOVE | SWP_NOZORDER | SWP_NOACTIVATE,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> Result<(i32, i32)> {
+        let rect = self.get_window_rect()?;
+        Ok((rect.right - rect.left, rect.bottom - rect.top))
+    }
+
+    /// The smallest the user can make the window, including
+    /// its frame, or `None` for no limit
+    fn set_min_size(&mut self, size: Option<(i32, i32)>) {
+        self.base().chrome.min_size.set(size);
+    }
+
+    fn min_size(&self) -> Option<(i32, i32)> {
+        self.base().chrome.min_size.get()
+    }
+
+    /// The largest the user can make the window, including
+    /// its frame, or `None` for no limit
+    fn set_max_size(&mut self, size: Option<(i32, i32)>) {
+        self.base().chrome.max_size.set(size);
+    }
+
+    fn max_size(&self) -> Option<(i32, i32)> {
+        self.base().chrome.max_size.get()
+    }
+
+    fn minimize(&self) -> bool {
+        unsafe { ShowWindow(self.get_hwnd(), SW_MINIMIZE).as_bool() }
+    }
+
+    fn maximize(&self) -> bool {
+        unsafe { ShowWindow(self.get_hwnd(), SW_MAXIMIZE).as_bool() }
+    }
+
+    fn restore(&self) -> bool {
+        unsafe { ShowWindow(self.get_hwnd(), SW_RESTORE).as_bool() }
+    }
+
+    fn is_minimized(&self) -> bool {
+        unsafe { IsIconic(self.get_hwnd()).as_bool() }
+    }
+
+    fn is_maximized(&self) -> bool {
+        unsafe { IsZoomed(self.get_hwnd()).as_bool() }
+    }
+
+    /// Switches between borderless fullscreen, covering the monitor
+    /// the window is on, and the window's previous style and placement
+    fn set_fullscreen(&mut self, fullscreen: bool) -> Result<()> {
+        let hwnd = self.get_hwnd();
+        match (fullscreen, self.get_base().windowed.take()) {
+            (true, None) => {
+                let style = unsafe { GetWindowLongPtrW(hwnd, GWL_STYLE) };
+                let mut placement = WINDOWPLACEMENT {
+                    length: mem::size_of::<WINDOWPLACEMENT>() as u32,
+                    ..Default::default()
+                };
+                let monitor = Monitor::from_window(hwnd)?.rect;
+                unsafe {
+                    GetWindowPlacement(hwnd, &mut placement)?;
+                    SetWindowLongPtrW(hwnd, GWL_STYLE, style & !(WS_OVERLAPPEDWINDOW.0 as isize));
+                    let entered = SetWindowPos(
+                        hwnd,
+                        HWND_TOP,
+                        monitor.left,
+                        monitor.top,
+                        monitor.right - monitor.left,
+                        monitor.bottom - monitor.top,
+                        SWP_NOOWNERZORDER | SWP_FRAMECHANGED,
+                    );
+                    if let Err(err) = entered {
+                        SetWindowLongPtrW(hwnd, GWL_STYLE, style);
+                        return Err(err.into());
+                    }
+                }
+                self.get_base().windowed = Some(Windowed { style, placement });
+            }
+            (false, Some(windowed)) => unsafe {
+                let fullscreen_style = GetWindowLongPtrW(hwnd, GWL_STYLE);
+                SetWindowLongPtrW(hwnd, GWL_STYLE, windowed.style);
+                let restored = SetWindowPlacement(hwnd, &windowed.placement).and_then(|()| {
+                    SetWindowPos(
+                        hwnd,
+                        None,
+                        0,
+                        0,
+                        0,
+                        0,
+                        SWP_NOMOVE
+                            | SWP_NOSIZE
+                            | SWP_NOZORDER
+                            | SWP_NOOWNERZORDER
+                            | SWP_FRAMECHANGED,
+                    )
+                });
+                if let Err(err) = restored {
+                    // still fullscreen, so it can be restored later
+                    SetWindowLongPtrW(hwnd, GWL_STYLE, fullscreen_style);
+                    self.get_base().windowed = Some(windowed);
+                    return Err(err.into());
+                }
+            },
+            // already as asked
+            (_, windowed) => self.get_base().windowed = windowed,
+        }
+        Ok(())
+    }
+
+    fn is_fullscreen(&self) -> bool {
+        self.base().windowed.is_some()
+    }
+
+    /// Centres the window in the work area of its monitor
+    fn center_on_screen(&self) -> Result<()> {
+        let work_area = self.monitor()?.work_area;
+        let (width, height) = self.size()?;
+        self.set_position(
+            work_area.left + (work_area.right - work_area.left - width) / 2,
+            work_area.top + (work_area.bottom - work_area.top - height) / 2,
+        )
+    }
+
+    fn set_always_on_top(&self, on_top: bool) -> Result<()> {
+        let insert_after = if on_top { HWND_TOPMOST } else { HWND_NOTOPMOST };
+        unsafe {
+            SetWindowPos(
+                self.get_hwnd(),
+                insert_after,
+                0,
+                0,
+                0,
+                0,
+                SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn is_always_on_top(&self) -> bool {
+        let ex_style = unsafe { GetWindowLongPtrW(self.get_hwnd(), GWL_EXSTYLE) };
+        ex_style as u32 & WS_EX_TOPMOST.0 != 0
+    }
+
+    /// Makes the whole window translucent, from `0.0` for
+    /// invisible to `1.0` for opaque, see [`layered`](super::layered)
+    fn set_opacity(&self, opacity: f32) -> Result<()> {
+        layered::set_opacity(self.get_hwnd(), opacity)
+    }
+
+    /// Replaces the window's content with `width` × `height` RGBA
+    /// pixels with per-pixel alpha, resizing the window to fit
+    fn update_layered(&self, rgba: &[u8], width: u32, height: u32, opacity: f32) -> Result<()> {
+        layered::update(self.get_hwnd(), rgba, width, height, opacity)
+    }
+
+    /// Lets every click pass through the window
+    /// to whatever is underneath it
+    fn set_click_through(&self, click_through: bool) -> Result<()> {
+        layered::set_click_through(self.get_hwnd(), click_through)
+    }
+
+    /// Parts of the client area, in client coordinates (logical
+    /// units if the window uses them), that clicks pass through to
+    /// the windows underneath. Clicks only reach other apps' windows
+    /// through fully transparent pixels of a per-pixel layered
+    /// window, or with `set_click_through`.
+    fn set_click_through_regions(&mut self, regions: Vec<RECT>) {
+        *self.base().chrome.click_through_regions.borrow_mut() = regions;
+    }
+
+    /// Replaces one of the window's icons, or goes back to
+    /// the class's icon with `None`, see [`icon`](super::icon)
+    fn set_icon(&mut self, size: IconSize, icon: Option<Icon>) {
+        let handle = icon.as_ref().map_or(HICON::default(), |icon| icon.handle());
+        unsafe {
+            SendMessageW(
+                self.get_hwnd(),
+                WM_SETICON,
+                size.wparam(),
+                LPARAM(handle.0 as isize),
             );
         }
-        hwnd
+        // the window doesn't own the icon, so it's kept until it's replaced
+        let base = self.get_base();
+        match size {
+            IconSize::Large => base.large_icon = icon,
+            IconSize::Small => base.small_icon = icon,
+        }
+    }
+
+    /// Makes the window scrollable over content of this size,
+    /// in the units `on_paint` draws in, see [`scroll`](super::scroll)
+    fn set_content_size(&mut self, width: i32, height: i32) {
+        scroll::set_content_size(self, width, height);
+    }
+
+    /// How far the content is scrolled, or `(0, 0)` if
+    /// the window isn't scrollable
+    fn scroll_offset(&self) -> (i32, i32) {
+        self.base().scroll.map_or((0, 0), |scroll| scroll.offset)
+    }
+
+    /// Scrolls the content so `x`, `y` is at the top left
+    /// of the client area, as far as it'll go
+    fn set_scroll_offset(&mut self, x: i32, y: i32) {
+        scroll::scroll_to(self, x, y);
+    }
+
+    /// Sets how far the scroll bar arrows and the mouse wheel scroll.
+    /// It's a line of the window's text unless it's set.
+    fn set_scroll_line_size(&mut self, x: i32, y: i32) {
+        if let Some(scroll) = &mut self.get_base().scroll {
+            scroll.line = Some((x, y));
+        }
+    }
+
+    /// Shows `cursor` over the client area instead of the class
+    /// cursor, or goes back to the class cursor with `None`. It's
+    /// shown the next time the mouse moves.
+    fn set_cursor(&mut self, cursor: Option<Cursor>) {
+        self.get_base().cursor = cursor;
+    }
+
+    /// Shows a different cursor over parts of the client area, in
+    /// client coordinates (logical units if the window uses them).
+    /// The first region the mouse is over wins.
+    fn set_cursor_regions(&mut self, regions: Vec<(RECT, Cursor)>) {
+        self.get_base().cursor_regions = regions;
+    }
+
+    /// Says what part of the window `point`, in client coordinates
+    /// (logical units if the window uses them), is over. `None`
+    /// leaves it to Windows, or for a custom frame treats the edges
+    /// as resize borders. See [`chrome`](super::chrome).
+    fn on_hit_test(&mut self, _point: POINT) -> Option<HitTest> {
+        None
+    }
+
+    /// Removes the standard title bar and border so the window
+    /// can draw its own, see [`chrome`](super::chrome)
+    fn set_custom_frame(&mut self, custom_frame: bool) -> Result<()> {
+        self.base().chrome.custom_frame.set(custom_frame);
+        unsafe {
+            SetWindowPos(
+                self.get_hwnd(),
+                None,
+                0,
+                0,
+                0,
+                0,
+                SWP_NOMOVE | SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE | SWP_FRAMECHANGED,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Extends the DWM frame into the client area by the given
+    /// margins, or over the whole window with `-1`s
+    fn extend_frame_into_client_area(
+        &self,
+        left: i32,
+        right: i32,
+        top: i32,
+        bottom: i32,
+    ) -> Result<()> {
+        chrome::extend_frame(self.get_hwnd(), left, right, top, bottom)
+    }
+
+    /// The monitor the window is on, or mostly on
+    fn monitor(&self) -> Result<Monitor> {
+        Monitor::from_window(self.get_hwnd())
+    }
+
+    /// The DPI of the monitor the window is on, or 96 unless the
+    /// app is DPI aware, see [`dpi`](super::dpi)
+    fn dpi(&self) -> u32 {
+        dpi::window_dpi(self.get_hwnd())
+    }
+
+    /// How much bigger than at 96 DPI the window should be drawn
+    fn scale_factor(&self) -> f32 {
+        dpi::scale_factor(self.dpi())
+    }
+
+    /// Called when the window's DPI changes, usually because it's been
+    /// moved to another monitor, with a suggested new rect in screen
+    /// coordinates. `NotHandled` moves the window to the suggested rect.
+    /// If the window was borrowed when the DPI changed, it's already
+    /// been moved and this is passed the rect it was moved to.
+    fn on_dpi_changed(&mut self, _dpi: u32, _suggested: &RECT) -> EventHandled {
+        EventHandled::NotHandled
     }
 
-    fn dispatch_event(&mut self, event: &Event) -> LRESULT {
-        if self.get_canary() != 99 {
-            println!("error: canary is not 99!");
-            return LRESULT(1);
+    /// Has `on_paint`, `on_resize` and `on_mouse` use logical units,
+    /// which are pixels at 96 DPI, rather than device pixels. Window
+    /// positions and sizes stay in device pixels.
+    fn set_logical_units(&mut self, logical_units: bool) {
+        self.base().chrome.logical_units.set(logical_units);
+        unsafe {
+            let _ = InvalidateRect(self.get_hwnd(), None, true);
         }
+    }
+
+    /// Called when monitors are added, removed, rearranged or change
+    /// resolution, or a work area changes, with the monitors as they
+    /// now are. Windows stay where they were, even if that's off screen.
+    fn on_display_change(&mut self, _monitors: &[Monitor]) -> EventHandled {
+        EventHandled::NotHandled
+    }
+
+    /// Where the window is, to be saved and later
+    /// passed to `set_placement`
+    fn placement(&self) -> Result<WindowPlacement> {
+        placement::get(self.get_hwnd())
+    }
+
+    /// Moves the window back to a saved placement, see
+    /// [`placement`](super::placement)
+    fn set_placement(&self, placement: &WindowPlacement) -> Result<()> {
+        placement::set(self.get_hwnd(), placement)
+    }
+
+    /// Passes the message to the window's handlers. Anything
+    /// left `NotHandled` gets the default window procedure.
+    fn dispatch_event(&mut self, event: &Event) -> EventHandled {
         let processed_event = match event.message {
             WM_CREATE => {
                 self.get_base().on_create(event);
@@ -187,14 +728,44 @@ pub trait Win {
             }
             WM_PAINT => {
                 let mut hdc = DeviceContext::begin_paint(self.get_hwnd());
-                let handled = match self.get_client_rect() {
+                let logical_dpi = match self.base().chrome.logical_units.get() {
+                    true => Some(self.dpi()),
+                    false => None,
+                };
+                let scroll_offset = self.get_base().scroll.map(|scroll| scroll.offset);
+                let rect = self.get_client_rect().and_then(|rect| {
+                    let mut rect = match logical_dpi {
+                        Some(dpi) => {
+                            hdc.set_logical_units(dpi)?;
+                            RECT {
+                                left: 0,
+                                top: 0,
+                                right: dpi::to_logical(rect.right, dpi),
+                                bottom: dpi::to_logical(rect.bottom, dpi),
+                            }
+                        }
+                        None => rect,
+                    };
+                    // draw in content coordinates, passing the visible part
+                    if let Some((x, y)) = scroll_offset {
+                        let (dx, dy) = match logical_dpi {
+                            Some(dpi) => (dpi::to_physical(x, dpi), dpi::to_physical(y, dpi)),
+                            None => (x, y),
+                        };
+                        hdc.set_viewport_origin(-dx, -dy)?;
+                        unsafe {
+                            let _ = OffsetRect(&mut rect, x, y);
+                        }
+                    }
+                    Ok(rect)
+                });
+                match rect {
                     Err(err) => {
-                        println!("error getting client rect: {:?}", err);
+                        error::report(err);
                         EventHandled::NotHandled
                     }
                     Ok(mut rect) => self.on_paint(&mut hdc, &mut rect),
-                };
-                handled
+                }
                 // let mut ps: PAINTSTRUCT = PAINTSTRUCT::default();
                 // let hdc: HDC;
                 // unsafe {
@@ -210,13 +781,76 @@ pub trait Win {
                 // }
             }
             WM_SIZE => {
-                let x = lword(event.lparam.0);
-                let y = hword(event.lparam.0);
+                let (mut x, mut y) = (lword(event.lparam.0), hword(event.lparam.0));
+                if self.base().chrome.logical_units.get() {
+                    let dpi = self.dpi();
+                    (x, y) = (dpi::to_logical(x, dpi), dpi::to_logical(y, dpi));
+                }
                 self.get_base().on_resize(x, y);
                 self.on_resize(x, y)
             }
-            WM_DESTROY => self.on_destroy(event),
-            WM_NCDESTROY => self.on_ncdestroy(event),
+            WM_ERASEBKGND => match (self.get_base().background_color, self.get_client_rect()) {
+                (Some(hex_color), Ok(rect)) => match Brush::solid(hex_color) {
+                    Ok(brush) => unsafe {
+                        FillRect(HDC(event.wparam.0 as _), &rect, brush.handle);
+                        EventHandled::Handled(LRESULT(1))
+                    },
+                    Err(err) => {
+                        error::report(err);
+                        EventHandled::NotHandled
+                    }
+                },
+                _ => EventHandled::NotHandled,
+            },
+            WM_HSCROLL | WM_VSCROLL => scroll::on_scroll(self, event),
+            WM_MOUSEWHEEL | WM_MOUSEHWHEEL => scroll::on_wheel(self, event),
+            WM_SETCURSOR => cursor::set_cursor(self, event),
+            WM_NCHITTEST => chrome::hit_test(self, event),
+            WM_NCCALCSIZE => chrome::calc_size(&self.base().chrome, event),
+            WM_NCLBUTTONDOWN | WM_NCLBUTTONUP => chrome::button_click(self, event),
+            WM_GETMINMAXINFO => chrome::min_max_info(&self.base().chrome, event),
+            WM_CLOSE => match self.on_close_requested() {
+                CloseRequest::Close => {
+                    dialog::closing(event.hwnd);
+                    EventHandled::NotHandled
+                }
+                CloseRequest::Cancel => EventHandled::Handled(LRESULT(0)),
+            },
+            WM_TIMER => timer::fire(self, event.wparam.0),
+            WM_DPICHANGED => dpi::dpi_changed(self, event),
+            WM_DISPLAYCHANGE => self.on_display_change(&monitor::monitors()),
+            WM_SETTINGCHANGE if event.wparam.0 == SPI_SETWORKAREA.0 as usize => {
+                self.on_display_change(&monitor::monitors())
+            }
+            WM_DESTROY => {
+                self.get_base().timers.clear(event.hwnd);
+                self.get_base().user_events.close();
+                tray::remove_all(event.hwnd);
+                self.on_destroy(event)
+            }
+            WM_NCDESTROY => {
+                executor::discard_updates(event.hwnd);
+                mdi::frame_destroyed(event.hwnd);
+                self.on_ncdestroy(event)
+            }
+            WM_USER_EVENT => {
+                while let Some(user_event) = self.get_base().user_events.next() {
+                    self.on_user_event(user_event);
+                }
+                EventHandled::Handled(LRESULT(0))
+            }
+            WM_TRAY => match tray::event(event) {
+                Some(tray_event) => self.on_tray_event(tray_event),
+                None => EventHandled::NotHandled,
+            },
+            WM_EXECUTOR_WAKE => {
+                executor::run_ready();
+                EventHandled::Handled(LRESULT(0))
+            }
+            WM_EXECUTOR_UPDATE => {
+                executor::apply_updates(self);
+                EventHandled::Handled(LRESULT(0))
+            }
             WM_COMMAND => {
                 let command_type = match hword(event.wparam.0 as isize) {
                     0 => SourceType::Menu,
@@ -232,32 +866,36 @@ pub trait Win {
                     },
                     source_type: command_type,
                 };
-                self.on_command(&command_event)
+                let handled = match command_event.source_type {
+                    SourceType::Menu | SourceType::Accelerator => {
+                        menu::command(self, command_event.command as u16)
+                    }
+                    SourceType::Control => None,
+                };
+                handled.unwrap_or_else(|| self.on_command(&command_event))
             }
             WM_MOUSEMOVE | WM_LBUTTONDOWN | WM_LBUTTONUP | WM_LBUTTONDBLCLK | WM_RBUTTONDOWN
             | WM_RBUTTONUP | WM_RBUTTONDBLCLK | WM_MBUTTONDBLCLK | WM_MBUTTONDOWN
-            | WM_MBUTTONUP => self.on_mouse(MouseEvent::new(event)),
+            | WM_MBUTTONUP => {
+                let mut mouse_event = MouseEvent::new(event);
+                if self.base().chrome.logical_units.get() {
+                    let dpi = self.dpi();
+                    let (x, y) = mouse_event.point;
+                    mouse_event.point = (dpi::to_logical(x, dpi), dpi::to_logical(y, dpi));
+                }
+                self.on_mouse(mouse_event)
+            }
             WM_KEYDOWN | WM_KEYUP | WM_CHAR | WM_DEADCHAR => self.on_kbd(KbdEvent::new(event)),
             _ => EventHandled::NotHandled,
         };
+        trace::trace_message(event, processed_event != EventHandled::NotHandled);
 
         if event.message == WM_NCDESTROY {
-            println!("WM_NCDESTROY");
-            unsafe {
-                SetWindowLongPtrW(event.hwnd, GWLP_USERDATA, 0);
-            }
             self.set_hwnd(HWND::default());
-            return unsafe {
-                DefWindowProcW(event.hwnd, event.message, event.wparam, event.lparam)
-            };
-        }
-
-        match processed_event {
-            EventHandled::NotHandled => unsafe {
-                DefWindowProcW(event.hwnd, event.message, event.wparam, event.lparam)
-            },
-            EventHandled::Handled(lresult) => lresult,
+            top_level::window_destroyed(event.hwnd);
+            return EventHandled::NotHandled;
         }
+        processed_event
     }
 
     extern "system" fn wndproc(
@@ -266,58 +904,7 @@ pub trait Win {
         wparam: WPARAM,
         lparam: LPARAM,
     ) -> LRESULT {
-        unsafe {
-            if hwnd == HWND::default() {
-                return DefWindowProcW(hwnd, message, wparam, lparam);
-            }
-        }
-
-        let ptr_self = match message {
-            WM_NCCREATE => {
-                println!("NC Create");
-                unsafe {
-                    let createstruct = &mut *(lparam.0 as *mut CREATESTRUCTW);
-
-                    println!("nc create raw_ptr: {:?}", createstruct.lpCreateParams);
-                    let ptr_self = Self::to_self_ptr(createstruct.lpCreateParams);
-
-                    SetWindowLongPtrW(hwnd, GWLP_USERDATA, Self::raw_ptr_isize(ptr_self));
-                    let ref_self = &mut *ptr_self;
-
-                    // println!("my_num in wndproc: {:?}", ref_self.my_num);
-                    ref_self.set_hwnd(hwnd.clone());
-
-                    // let window_obj = Rc::from_raw(createstruct.lpCreateParams as *const &WPWin);
-                    // println!("window (in proc) hwnd: {:?}", window_obj.get_hwnd());
-                    // println!("proc hwnd: {:?}", window);
-                    // println!("my_num: {:?}", window_obj.my_num);
-                    ptr_self
-                }
-                //LRESULT(1)
-            }
-            _ => unsafe {
-                Self::to_self_ptr(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut ::core::ffi::c_void)
-            },
-        };
-
-        if ptr_self.is_null() {
-            println!("ptr_self is null");
-            unsafe {
-                return DefWindowProcW(hwnd, message, wparam, lparam);
-            }
-        }
-        let ref_self: &mut Self;
-        unsafe {
-            ref_self = &mut *ptr_self;
-        }
-
-        let event = super::Event {
-            hwnd: hwnd,
-            message: message,
-            wparam: wparam,
-            lparam: lparam,
-        };
-        ref_self.dispatch_event(&event)
+        handle::wndproc::<Self>(hwnd, message, wparam, lparam)
     }
 }
 
@@ -339,7 +926,7 @@ impl Component {
     fn create_element(&mut self, parent: HWND, instance: HINSTANCE) -> Result<()> {
         match self {
             Component::Element(el) => el.create_element(parent, instance),
-            Component::Container(con) => Ok(()),
+            Component::Container(_con) => Ok(()),
         }
     }
 }
@@ -409,7 +996,7 @@ impl Element for Button {
                     self.hwnd = hwnd;
                     Ok(())
                 }
-                Err(err) => Err(err),
+                Err(err) => Err(Error::WindowCreation(err)),
             }
         }
     }
@@ -433,34 +1020,21 @@ impl Win for MainWindow {
             created: false,
         }
     }
-    fn create_window_with_args(
-        &mut self,
-        title: PCWSTR,
-        create_args: &WinCreateArgs,
-    ) -> Result<HWND> {
-        self.create_win(title, create_args, self.inst)
-    }
-
-    fn set_child(&mut self, mut child: Component) {
+    fn set_child(&mut self, mut child: Component) -> Result<()> {
         if self.created {
-            child.create_element(self.get_hwnd(), self.inst).unwrap();
+            child.create_element(self.get_hwnd(), self.inst)?;
         }
         self.child = Some(child);
-    }
-
-    fn create_window(&mut self, title: PCWSTR) -> Result<HWND> {
-        let create_args = WinCreateArgs {
-            instance: self.inst.into(),
-            ..WinCreateArgs::default_win_main()
-        };
-        self.create_win(title, &create_args, self.inst)
+        Ok(())
     }
 
     fn on_create(&mut self, _event: &Event) -> EventHandled {
         self.created = true;
         let child = self.child.take();
         if let Some(mut child) = child {
-            child.create_element(self.get_hwnd(), self.inst);
+            if let Err(err) = child.create_element(self.get_hwnd(), self.inst) {
+                error::report(err);
+            }
             self.child = Some(child);
         };
         EventHandled::Handled(LRESULT(0))
@@ -3,32 +3,126 @@ use windows::{
     Win32::{Foundation::*, UI::WindowsAndMessaging::*},
 };
 
-use super::load_cursor;
+use super::{
+    cursor::{Cursor, SystemCursor},
+    error::{self, Error, Result},
+    icon::Icon,
+    placement::WindowPlacement,
+};
+
+/// A resource in the app's executable, by name or by numeric id
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResourceId {
+    Name(HSTRING),
+    Id(u16),
+}
+
+impl ResourceId {
+    /// The resource as passed to `LoadIcon` and friends, valid
+    /// for as long as `self` is
+    pub fn as_pcwstr(&self) -> PCWSTR {
+        match self {
+            ResourceId::Name(name) => PCWSTR(name.as_ptr()),
+            ResourceId::Id(id) => PCWSTR(*id as usize as *const u16),
+        }
+    }
+}
+
+impl From<&str> for ResourceId {
+    fn from(name: &str) -> Self {
+        ResourceId::Name(HSTRING::from(name))
+    }
+}
+
+impl From<String> for ResourceId {
+    fn from(name: String) -> Self {
+        ResourceId::Name(HSTRING::from(name))
+    }
+}
+
+impl From<u16> for ResourceId {
+    fn from(id: u16) -> Self {
+        ResourceId::Id(id)
+    }
+}
 
+/// How a window is first shown
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ShowState {
+    #[default]
+    Normal,
+    /// Left hidden until `Win::show` is called
+    Hidden,
+    Minimized,
+    Maximized,
+}
+
+impl ShowState {
+    pub(crate) fn show_cmd(self) -> SHOW_WINDOW_CMD {
+        match self {
+            ShowState::Normal => SW_NORMAL,
+            ShowState::Hidden => SW_HIDE,
+            ShowState::Minimized => SW_SHOWMINIMIZED,
+            ShowState::Maximized => SW_SHOWMAXIMIZED,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct WinCreateArgs {
-    pub class_name: PCWSTR,
+    /// The window class to use, or `None` for one of the
+    /// window type's own, see [`class`](super::class)
+    pub class_name: Option<String>,
     pub ex_style: WINDOW_EX_STYLE,
     pub style: WINDOW_STYLE,
     pub instance: HINSTANCE,
-    pub icon: Option<PCWSTR>,
-    pub cursor: HCURSOR,
-    pub menu_name: PCWSTR,
+    /// The class's icon resource, or `None` for the default
+    /// application icon
+    pub icon: Option<ResourceId>,
+    /// Icons from image data, shown instead of the class's icon
+    pub large_icon: Option<Icon>,
+    pub small_icon: Option<Icon>,
+    /// The class's cursor, which is kept until the class is unregistered
+    pub cursor: Option<Cursor>,
+    pub menu_name: Option<ResourceId>,
     pub window_height: i32,
     pub window_width: i32,
+    /// Where the window's top left corner goes, in screen coordinates,
+    /// or `None` to let Windows choose
+    pub position: Option<(i32, i32)>,
+    pub parent: Option<HWND>,
+    /// Fills the window's background, as `0x00BBGGRR`
+    pub background_color: Option<u32>,
+    pub show_state: ShowState,
+    /// Leaves out the standard title bar and border, see
+    /// [`chrome`](super::chrome)
+    pub custom_frame: bool,
+    /// A saved placement to restore the window to once it's created
+    pub placement: Option<WindowPlacement>,
 }
 
 impl Default for WinCreateArgs {
     fn default() -> Self {
         WinCreateArgs {
-            class_name: w!(""),
+            class_name: None,
             ex_style: WINDOW_EX_STYLE::default(),
             style: WINDOW_STYLE::default(),
             instance: HINSTANCE::default(),
             icon: None,
-            cursor: load_cursor(None, IDC_ARROW).unwrap(),
-            menu_name: w!(""),
+            large_icon: None,
+            small_icon: None,
+            cursor: Cursor::system(SystemCursor::Arrow)
+                .map_err(error::report)
+                .ok(),
+            menu_name: None,
             window_height: CW_USEDEFAULT,
             window_width: CW_USEDEFAULT,
+            position: None,
+            parent: None,
+            background_color: None,
+            show_state: ShowState::Normal,
+            custom_frame: false,
+            placement: None,
         }
     }
 }
@@ -36,12 +130,153 @@ impl Default for WinCreateArgs {
 impl WinCreateArgs {
     pub fn default_win_main() -> Self {
         WinCreateArgs {
-            class_name: w!("mainwin"),
-            menu_name: w!("AppMenu"),
-            icon: Some(w!("AppIcon")),
+            menu_name: Some("AppMenu".into()),
+            icon: Some("AppIcon".into()),
             ex_style: WS_EX_APPWINDOW,
             style: WS_OVERLAPPEDWINDOW,
             ..Default::default()
         }
     }
+
+    /// A fixed size popup with a caption and close button, for
+    /// running with `dialog::run_modal`
+    pub fn default_dialog() -> Self {
+        WinCreateArgs {
+            ex_style: WS_EX_DLGMODALFRAME | WS_EX_CONTROLPARENT,
+            style: WS_POPUP | WS_CAPTION | WS_SYSMENU,
+            window_width: 400,
+            window_height: 250,
+            ..Default::default()
+        }
+    }
+
+    /// A visible child window, clipped to its parent and its
+    /// siblings, which should be given a position and size, see
+    /// [`child`](super::child)
+    pub fn default_child() -> Self {
+        WinCreateArgs {
+            style: WS_CHILD | WS_VISIBLE | WS_CLIPSIBLINGS | WS_CLIPCHILDREN,
+            window_width: 0,
+            window_height: 0,
+            ..Default::default()
+        }
+    }
+
+    /// A document window inside an MDI frame, see [`mdi`](super::mdi)
+    pub fn default_mdi_child() -> Self {
+        WinCreateArgs {
+            ex_style: WS_EX_MDICHILD,
+            style: WS_OVERLAPPEDWINDOW | WS_CLIPCHILDREN,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_class_name(mut self, class_name: impl Into<String>) -> Self {
+        self.class_name = Some(class_name.into());
+        self
+    }
+    pub fn with_style(mut self, style: WINDOW_STYLE) -> Self {
+        self.style = style;
+        self
+    }
+    pub fn with_ex_style(mut self, ex_style: WINDOW_EX_STYLE) -> Self {
+        self.ex_style = ex_style;
+        self
+    }
+    pub fn with_instance(mut self, instance: HINSTANCE) -> Self {
+        self.instance = instance;
+        self
+    }
+    pub fn with_icon(mut self, icon: impl Into<ResourceId>) -> Self {
+        self.icon = Some(icon.into());
+        self
+    }
+    /// Sets the window's Alt+Tab icon, see [`icon`](super::icon)
+    pub fn with_large_icon(mut self, icon: Icon) -> Self {
+        self.large_icon = Some(icon);
+        self
+    }
+    /// Sets the window's title bar and taskbar icon
+    pub fn with_small_icon(mut self, icon: Icon) -> Self {
+        self.small_icon = Some(icon);
+        self
+    }
+    pub fn with_cursor(mut self, cursor: Cursor) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+    pub fn with_menu(mut self, menu_name: impl Into<ResourceId>) -> Self {
+        self.menu_name = Some(menu_name.into());
+        self
+    }
+    pub fn without_menu(mut self) -> Self {
+        self.menu_name = None;
+        self
+    }
+    pub fn with_size(mut self, width: i32, height: i32) -> Self {
+        self.window_width = width;
+        self.window_height = height;
+        self
+    }
+    pub fn with_position(mut self, x: i32, y: i32) -> Self {
+        self.position = Some((x, y));
+        self
+    }
+    pub fn with_parent(mut self, parent: HWND) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+    pub fn with_background_color(mut self, hex_color: u32) -> Self {
+        self.background_color = Some(hex_color);
+        self
+    }
+    pub fn with_show_state(mut self, show_state: ShowState) -> Self {
+        self.show_state = show_state;
+        self
+    }
+    pub fn with_custom_frame(mut self) -> Self {
+        self.custom_frame = true;
+        self
+    }
+    pub fn with_placement(mut self, placement: WindowPlacement) -> Self {
+        self.placement = Some(placement);
+        self
+    }
+
+    /// Checks for combinations `CreateWindowExW` would reject
+    /// or quietly ignore, before a window is created
+    pub fn validate(&self) -> Result<()> {
+        let child = self.style.contains(WS_CHILD);
+        if child && self.style.contains(WS_POPUP) {
+            return Err(Error::InvalidCreateArgs(
+                "WS_CHILD and WS_POPUP can't be combined",
+            ));
+        }
+        if child && self.parent.is_none() {
+            return Err(Error::InvalidCreateArgs("a WS_CHILD window needs a parent"));
+        }
+        if self.style.contains(WS_MINIMIZE | WS_MAXIMIZE) {
+            return Err(Error::InvalidCreateArgs(
+                "WS_MINIMIZE and WS_MAXIMIZE can't be combined",
+            ));
+        }
+        if child && self.show_state != ShowState::Normal && self.show_state != ShowState::Hidden {
+            return Err(Error::InvalidCreateArgs(
+                "a WS_CHILD window can't start minimized or maximized",
+            ));
+        }
+        let default_size =
+            self.window_width == CW_USEDEFAULT || self.window_height == CW_USEDEFAULT;
+        if default_size && (child || self.style.contains(WS_POPUP)) {
+            return Err(Error::InvalidCreateArgs(
+                "only overlapped windows can have a default size",
+            ));
+        }
+        if (self.window_width < 0 && self.window_width != CW_USEDEFAULT)
+            || (self.window_height < 0 && self.window_height != CW_USEDEFAULT)
+        {
+            return Err(Error::InvalidCreateArgs("the size can't be negative"));
+        }
+        Ok(())
+    }
 }